@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+/// splits a structured comment of ssh-view, "#@<key> <value>", ssh ignores them as any other comment
+pub fn annotation(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim().strip_prefix("#@")?;
+    let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    Some((key, value.trim()))
+}
+
+/// returns the values of the structured comments of the Host block with the index, in the order they are written
+pub fn read_annotations(config_file: &Path, index: usize, key: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(block_lines(read_to_string(config_file)?.as_str(), index)
+        .into_iter()
+        .filter_map(|line| annotation(line).filter(|(name, _)| *name == key).map(|(_, value)| value.to_string()))
+        .collect())
+}
+
+/// replaces the structured comments of the Host block with the index, no values removes them,
+/// new comments are written right after the Host line with the indentation of the block
+pub fn write_annotations(config_file: &Path, index: usize, key: &str, values: &[String]) -> Result<(), Box<dyn Error>> {
+    let data = read_to_string(config_file)?;
+    let indentation = block_lines(data.as_str(), index)
+        .into_iter()
+        .find(|line| !line.trim().is_empty())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .filter(|indentation| !indentation.is_empty())
+        .unwrap_or("    ");
+    let mut output = String::with_capacity(data.len());
+    let mut block = None;
+    for line in data.lines() {
+        if line.trim().starts_with("Host ") {
+            block = Some(block.map_or(0, |block| block + 1));
+            output.push_str(line);
+            output.push('\n');
+            if block == Some(index) {
+                for value in values {
+                    output.push_str(format!("{indentation}#@{key} {value}").trim_end());
+                    output.push('\n');
+                }
+            }
+            continue;
+        }
+        if block == Some(index) && annotation(line).is_some_and(|(name, _)| name == key) {
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    if !matches!(block, Some(block) if block >= index) {
+        return Err(format!("there is no host with the index {index}").into());
+    }
+    if !data.ends_with('\n') {
+        output.pop();
+    }
+    write(config_file, output)?;
+    Ok(())
+}
+
+/// returns the lines of the Host block with the index, without the Host line
+pub fn block_lines(data: &str, index: usize) -> Vec<&str> {
+    let mut block = None;
+    let mut lines = vec![];
+    for line in data.lines() {
+        if line.trim().starts_with("Host ") {
+            block = Some(block.map_or(0, |block| block + 1));
+        } else if block == Some(index) {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFile;
+
+    const CONFIG: &str = "Host web\n    HostName 10.0.0.1\nHost db\n\t#@note old\n\tHostName 10.0.0.2\n\t#@note older\n";
+
+    #[test]
+    fn write_annotations_replaces_the_comments_of_the_block() {
+        let file = TempFile::new("annotations-replace", CONFIG);
+        let path = file.path();
+        write_annotations(path, 1, "note", &[String::from("first"), String::from("second")]).unwrap();
+        let data = read_to_string(path).unwrap();
+        let notes = read_annotations(path, 1, "note").unwrap();
+        write_annotations(path, 0, "note", &[]).unwrap();
+        let unchanged = read_to_string(path).unwrap();
+        let missing = write_annotations(path, 2, "note", &[]);
+        assert_eq!(
+            data,
+            "Host web\n    HostName 10.0.0.1\nHost db\n\t#@note first\n\t#@note second\n\tHostName 10.0.0.2\n"
+        );
+        assert_eq!(notes, ["first", "second"]);
+        assert_eq!(unchanged, data);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn write_annotations_keeps_a_missing_last_newline() {
+        let file = TempFile::new("annotations-newline", "Host web\n    HostName 10.0.0.1");
+        let path = file.path();
+        write_annotations(path, 0, "description", &[String::from("web server")]).unwrap();
+        let data = read_to_string(path).unwrap();
+        assert_eq!(data, "Host web\n    #@description web server\n    HostName 10.0.0.1");
+    }
+
+    #[test]
+    fn annotation_of_a_line() {
+        assert_eq!(annotation("    #@tags prod,db "), Some(("tags", "prod,db")));
+        assert_eq!(annotation("#@note"), Some(("note", "")));
+        assert_eq!(annotation("# tags prod"), None);
+    }
+}
@@ -0,0 +1,668 @@
+use crate::ssh_config::{effective_option, split_option, SshHost};
+use crate::tags::split_tags;
+use clap::ValueEnum;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+/// group every host of an inventory belongs to
+const ALL: &str = "all";
+/// flags of ssh that take a value
+const SSH_FLAGS_WITH_VALUE: &str = "BbcDEeFIiJLlmOopQRSWw";
+/// group of the hosts of an INI inventory that are before any group
+const UNGROUPED: &str = "ungrouped";
+
+/// Group of an inventory with the hosts and the groups in it and the variables it sets to them
+#[derive(Debug, Default)]
+struct Group {
+    hosts: Vec<String>,
+    children: Vec<String>,
+    vars: BTreeMap<String, String>,
+}
+
+/// Groups and hosts of an inventory before the variables of the groups are given to the hosts
+#[derive(Debug, Default)]
+struct Inventory {
+    groups: BTreeMap<String, Group>,
+    hosts: Vec<String>,
+    host_vars: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Inventory {
+    fn group(&mut self, name: &str) -> &mut Group {
+        self.groups.entry(name.to_string()).or_default()
+    }
+
+    fn add_host(&mut self, group: &str, host: &str, vars: BTreeMap<String, String>) {
+        if !self.hosts.iter().any(|name| name == host) {
+            self.hosts.push(host.to_string());
+        }
+        let group = self.group(group);
+        if !group.hosts.iter().any(|name| name == host) {
+            group.hosts.push(host.to_string());
+        }
+        self.host_vars.entry(host.to_string()).or_default().extend(vars);
+    }
+}
+
+/// Host of an inventory with every group it belongs to and its variables, the ones of its groups included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryHost {
+    pub name: String,
+    /// groups of the host and the groups they are in, without "all" and "ungrouped"
+    pub groups: Vec<String>,
+    pub vars: BTreeMap<String, String>,
+}
+
+impl InventoryHost {
+    /// returns the first variable that is set, ansible accepts older names for some of them
+    pub fn var(&self, names: &[&str]) -> Option<&str> {
+        names.iter().find_map(|name| self.vars.get(*name)).map(|value| value.as_str())
+    }
+}
+
+/// reads an INI or a YAML inventory, YAML is used for .yml and .yaml files and for the files that start with a mapping
+pub fn parse_inventory(path: &Path, data: &str) -> Result<Vec<InventoryHost>, Box<dyn Error>> {
+    let yaml = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yml") | Some("yaml") => true,
+        Some("ini") => false,
+        _ => data
+            .lines()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty() && !line.starts_with(['#', ';']) && line != &"---")
+            .is_some_and(|line| line.ends_with(':') && !line.starts_with('[')),
+    };
+    let inventory = match yaml {
+        true => parse_yaml(data)?,
+        false => parse_ini(data),
+    };
+    Ok(resolve(&inventory))
+}
+
+/// gives every host the variables of its groups, the variables of a group override the ones of the groups it is in
+/// and the variables of the host override all of them, as ansible does
+fn resolve(inventory: &Inventory) -> Vec<InventoryHost> {
+    let mut parents: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (name, group) in inventory.groups.iter() {
+        for child in group.children.iter() {
+            parents.entry(child.as_str()).or_default().push(name.as_str());
+        }
+    }
+    inventory
+        .hosts
+        .iter()
+        .map(|host| {
+            // (depth, group) of the groups of the host and the groups above them
+            let mut groups: Vec<(usize, &str)> = vec![];
+            let mut pending = inventory
+                .groups
+                .iter()
+                .filter(|(_, group)| group.hosts.contains(host))
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<&str>>();
+            while let Some(group) = pending.pop() {
+                if groups.iter().any(|(_, name)| *name == group) {
+                    continue;
+                }
+                groups.push((depth(group, &parents, 0), group));
+                pending.extend(parents.get(group).cloned().unwrap_or_default());
+            }
+            groups.sort();
+            let mut vars = inventory.groups.get(ALL).map(|all| all.vars.clone()).unwrap_or_default();
+            for (_, group) in groups.iter() {
+                if let Some(group) = inventory.groups.get(*group) {
+                    vars.extend(group.vars.clone());
+                }
+            }
+            vars.extend(inventory.host_vars.get(host).cloned().unwrap_or_default());
+            InventoryHost {
+                name: host.clone(),
+                groups: groups
+                    .iter()
+                    .map(|(_, group)| group.to_string())
+                    .filter(|group| group != ALL && group != UNGROUPED)
+                    .collect(),
+                vars,
+            }
+        })
+        .collect()
+}
+
+/// how many groups are above the group, the groups without parents are children of "all"
+fn depth(group: &str, parents: &BTreeMap<&str, Vec<&str>>, visited: usize) -> usize {
+    // the visited groups stop the loops of groups that are children of each other
+    if group == ALL || visited > parents.len() {
+        return 0;
+    }
+    let above = parents
+        .get(group)
+        .and_then(|groups| groups.iter().map(|parent| depth(parent, parents, visited + 1)).max())
+        .unwrap_or_default();
+    above + 1
+}
+
+/// reads "[group]", "[group:vars]" and "[group:children]" sections, the hosts can have "key=value" variables after them
+fn parse_ini(data: &str) -> Inventory {
+    let mut inventory = Inventory::default();
+    let mut section = (String::from(UNGROUPED), "");
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = match header.rsplit_once(':') {
+                Some((group, "vars")) => (group.to_string(), "vars"),
+                Some((group, "children")) => (group.to_string(), "children"),
+                _ => (header.to_string(), ""),
+            };
+            inventory.group(section.0.as_str());
+            continue;
+        }
+        match section.1 {
+            "vars" => {
+                if let Some((key, value)) = line.split_once('=') {
+                    let value = split_words(value.trim()).join(" ");
+                    inventory.group(section.0.as_str()).vars.insert(key.trim().to_string(), value);
+                }
+            }
+            "children" => {
+                inventory.group(line);
+                inventory.group(section.0.as_str()).children.push(line.to_string());
+            }
+            _ => {
+                let words = split_words(line);
+                let vars = words
+                    .iter()
+                    .skip(1)
+                    .filter_map(|word| word.split_once('='))
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect::<BTreeMap<String, String>>();
+                for host in expand_range(words[0].as_str()) {
+                    inventory.add_host(section.0.as_str(), host.as_str(), vars.clone());
+                }
+            }
+        }
+    }
+    inventory
+}
+
+/// reads the groups of a YAML inventory, each one with optional "hosts", "vars" and "children" mappings
+fn parse_yaml(data: &str) -> Result<Inventory, Box<dyn Error>> {
+    let mut inventory = Inventory::default();
+    let root: Value = serde_yaml::from_str(data)?;
+    let groups = match root {
+        Value::Mapping(groups) => groups,
+        Value::Null => return Ok(inventory),
+        _ => return Err("the inventory has to be a mapping of groups".into()),
+    };
+    for (name, group) in groups.iter() {
+        add_yaml_group(&mut inventory, scalar(name).as_str(), group);
+    }
+    Ok(inventory)
+}
+
+fn add_yaml_group(inventory: &mut Inventory, name: &str, group: &Value) {
+    inventory.group(name);
+    if let Some(Value::Mapping(hosts)) = group.get("hosts") {
+        for (host, vars) in hosts.iter() {
+            let vars = match vars {
+                Value::Mapping(vars) => vars.iter().map(|(key, value)| (scalar(key), scalar(value))).collect(),
+                _ => BTreeMap::new(),
+            };
+            for host in expand_range(scalar(host).as_str()) {
+                inventory.add_host(name, host.as_str(), vars.clone());
+            }
+        }
+    }
+    if let Some(Value::Mapping(vars)) = group.get("vars") {
+        let vars = vars.iter().map(|(key, value)| (scalar(key), scalar(value))).collect::<Vec<(String, String)>>();
+        inventory.group(name).vars.extend(vars);
+    }
+    if let Some(Value::Mapping(children)) = group.get("children") {
+        for (child, group) in children.iter() {
+            let child = scalar(child);
+            if !inventory.group(name).children.contains(&child) {
+                inventory.group(name).children.push(child.clone());
+            }
+            add_yaml_group(inventory, child.as_str(), group);
+        }
+    }
+}
+
+/// the text of a YAML scalar, the lists and mappings are written back as YAML
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Number(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::Null => String::new(),
+        value => serde_yaml::to_string(value).unwrap_or_default().trim().to_string(),
+    }
+}
+
+/// expands the "[01:03]" and "[a:c]" ranges of a host pattern, a third number is the step, "[1:9:2]"
+fn expand_range(pattern: &str) -> Vec<String> {
+    let range = pattern
+        .split_once('[')
+        .and_then(|(prefix, rest)| rest.split_once(']').map(|(range, suffix)| (prefix, range, suffix)));
+    let Some((prefix, range, suffix)) = range else {
+        return vec![pattern.to_string()];
+    };
+    let bounds = range.split(':').collect::<Vec<&str>>();
+    let step = bounds.get(2).and_then(|step| step.parse::<usize>().ok()).unwrap_or(1).max(1);
+    let values: Vec<String> = match (bounds.first(), bounds.get(1)) {
+        (Some(start), Some(end)) if start.parse::<u64>().is_ok() && end.parse::<u64>().is_ok() => {
+            let width = if start.starts_with('0') { start.len() } else { 0 };
+            (start.parse::<u64>().unwrap_or_default()..=end.parse::<u64>().unwrap_or_default())
+                .step_by(step)
+                .map(|number| format!("{number:0width$}"))
+                .collect()
+        }
+        (Some(start), Some(end)) if start.len() == 1 && end.len() == 1 => {
+            (start.chars().next().unwrap_or('a')..=end.chars().next().unwrap_or('a'))
+                .step_by(step)
+                .map(|letter| letter.to_string())
+                .collect()
+        }
+        _ => return vec![pattern.to_string()],
+    };
+    values
+        .iter()
+        .flat_map(|value| expand_range(format!("{prefix}{value}{suffix}").as_str()))
+        .collect()
+}
+
+/// splits the words of a line as a shell does, the quotes keep the spaces in a word and are removed
+pub fn split_words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(c) = chars.next() {
+                    word.get_or_insert_with(String::new).push(c);
+                }
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// options that host_options can set without "-o", the rest come from "-o" in the ssh args
+pub const IMPORTED_OPTIONS: &[&str] = &["HostName", "user", "port", "IdentityFile", "ProxyJump"];
+
+/// maps the connection variables of the host to ssh_config options, the warnings are the ones that couldn't be mapped
+pub fn host_options(host: &InventoryHost) -> (Vec<(String, String)>, Vec<String>) {
+    let mut options = vec![];
+    let mut warnings = vec![];
+    let mut add = |key: &str, value: &str, warnings: &mut Vec<String>| match value.contains("{{") {
+        true => warnings.push(format!("{key} isn't imported, \"{value}\" is a template")),
+        false => options.push((key.to_string(), value.to_string())),
+    };
+    add("HostName", host.var(&["ansible_host", "ansible_ssh_host"]).unwrap_or(host.name.as_str()), &mut warnings);
+    if let Some(user) = host.var(&["ansible_user", "ansible_ssh_user"]) {
+        add("user", user, &mut warnings);
+    }
+    if let Some(port) = host.var(&["ansible_port", "ansible_ssh_port"]) {
+        add("port", port, &mut warnings);
+    }
+    if let Some(key_file) = host.var(&["ansible_ssh_private_key_file", "ansible_private_key_file"]) {
+        add("IdentityFile", key_file, &mut warnings);
+    }
+    for variable in ["ansible_ssh_common_args", "ansible_ssh_extra_args"] {
+        let words = split_words(host.var(&[variable]).unwrap_or_default());
+        let mut words = words.iter();
+        while let Some(word) = words.next() {
+            // the options can have the value in the same word, "-oKey=Value", or in the next one
+            let (flag, value) = match word.strip_prefix('-').filter(|flag| flag.len() > 1 && !flag.starts_with('-')) {
+                Some(flag) => (&flag[..1], Some(flag[1..].to_string())),
+                None => (word.trim_start_matches('-'), None),
+            };
+            let key = match flag {
+                "o" => None,
+                "J" => Some("ProxyJump"),
+                "p" => Some("port"),
+                "i" => Some("IdentityFile"),
+                "l" => Some("user"),
+                _ => {
+                    // the value of the flags that have one goes with them
+                    let value = match (value, flag.len() == 1 && SSH_FLAGS_WITH_VALUE.contains(flag)) {
+                        (None, true) => words.next().map(|value| format!(" {value}")).unwrap_or_default(),
+                        (value, _) => value.unwrap_or_default(),
+                    };
+                    warnings.push(format!("\"{word}{value}\" of {variable} has no equivalent in ssh_config"));
+                    continue;
+                }
+            };
+            let Some(value) = value.or_else(|| words.next().cloned()) else {
+                warnings.push(format!("\"{word}\" of {variable} has no value"));
+                continue;
+            };
+            match key {
+                Some(key) => add(key, value.as_str(), &mut warnings),
+                None => match split_option(value.as_str()) {
+                    Some((key, value)) => add(key.as_str(), value.as_str(), &mut warnings),
+                    None => warnings.push(format!("\"-o {value}\" of {variable} couldn't be read")),
+                },
+            }
+        }
+    }
+    (options, warnings)
+}
+
+/// Format of the exported inventory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InventoryFormat {
+    /// Ansible INI inventory
+    Ansible,
+    /// Ansible YAML inventory
+    AnsibleYaml,
+}
+
+/// the host of the inventory for the connection name, with the options of every matching block resolved as ssh does
+pub fn inventory_host(alias: &str, tags: &str, ssh_hosts: &[SshHost]) -> InventoryHost {
+    let mut vars = BTreeMap::new();
+    let options = [
+        ("ansible_host", "HostName"),
+        ("ansible_user", "User"),
+        ("ansible_port", "Port"),
+        ("ansible_ssh_private_key_file", "IdentityFile"),
+    ];
+    for (var, option) in options {
+        if let Some(value) = effective_option(ssh_hosts, alias, option) {
+            vars.insert(var.to_string(), value);
+        }
+    }
+    let args = ["ProxyJump", "ProxyCommand"]
+        .iter()
+        .filter_map(|option| effective_option(ssh_hosts, alias, option).map(|value| (option, value)))
+        .filter(|(_, value)| !value.eq_ignore_ascii_case("none"))
+        .map(|(option, value)| match value.contains(char::is_whitespace) {
+            true => format!("-o {option}=\"{}\"", value.replace('"', "\\\"")),
+            false => format!("-o {option}={value}"),
+        })
+        .collect::<Vec<String>>();
+    if !args.is_empty() {
+        vars.insert(String::from("ansible_ssh_common_args"), args.join(" "));
+    }
+    InventoryHost {
+        name: alias.to_string(),
+        groups: split_tags(tags),
+        vars,
+    }
+}
+
+/// groups of the inventory with their hosts in the order of the config file, the hosts without groups go first
+fn inventory_groups(hosts: &[InventoryHost]) -> Vec<(Option<&str>, Vec<&InventoryHost>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&InventoryHost>)> = vec![(None, vec![])];
+    for host in hosts.iter() {
+        if host.groups.is_empty() {
+            groups[0].1.push(host);
+        }
+        for group in host.groups.iter() {
+            match groups.iter_mut().find(|(name, _)| *name == Some(group.as_str())) {
+                Some((_, hosts)) => hosts.push(host),
+                None => groups.push((Some(group.as_str()), vec![host])),
+            }
+        }
+    }
+    groups
+}
+
+/// writes an INI inventory, the variables of each host go in the line of its first group
+pub fn inventory_ini(hosts: &[InventoryHost]) -> String {
+    let mut inventory = String::new();
+    for (group, group_hosts) in inventory_groups(hosts) {
+        if group_hosts.is_empty() {
+            continue;
+        }
+        if let Some(group) = group {
+            inventory.push_str(format!("\n[{group}]\n").as_str());
+        }
+        for host in group_hosts {
+            inventory.push_str(host.name.as_str());
+            if host.groups.first().map(|first| first.as_str()) == group {
+                for (key, value) in host.vars.iter() {
+                    let value = match (value.contains(char::is_whitespace), value.contains('\'')) {
+                        (false, false) => value.clone(),
+                        (_, false) => format!("'{value}'"),
+                        (_, true) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+                    };
+                    inventory.push_str(format!(" {key}={value}").as_str());
+                }
+            }
+            inventory.push('\n');
+        }
+    }
+    inventory.trim_start().to_string()
+}
+
+/// writes a YAML inventory, the variables of each host go in its first group
+pub fn inventory_yaml(hosts: &[InventoryHost]) -> Result<String, Box<dyn Error>> {
+    let mut all = Mapping::new();
+    let mut children = Mapping::new();
+    for (group, group_hosts) in inventory_groups(hosts) {
+        if group_hosts.is_empty() {
+            continue;
+        }
+        let mut yaml_hosts = Mapping::new();
+        for host in group_hosts {
+            let vars = match host.groups.first().map(|first| first.as_str()) == group {
+                true => Value::Mapping(
+                    host.vars
+                        .iter()
+                        .map(|(key, value)| (Value::from(key.as_str()), yaml_scalar(value)))
+                        .collect(),
+                ),
+                false => Value::Null,
+            };
+            yaml_hosts.insert(Value::from(host.name.as_str()), vars);
+        }
+        let mut hosts = Mapping::new();
+        hosts.insert(Value::from("hosts"), Value::Mapping(yaml_hosts));
+        match group {
+            Some(group) => {
+                children.insert(Value::from(group), Value::Mapping(hosts));
+            }
+            None => all.extend(hosts),
+        }
+    }
+    if !children.is_empty() {
+        all.insert(Value::from("children"), Value::Mapping(children));
+    }
+    let mut root = Mapping::new();
+    root.insert(Value::from(ALL), Value::Mapping(all));
+    Ok(serde_yaml::to_string(&root)?)
+}
+
+/// the ports are written as numbers, the rest of the variables as strings
+fn yaml_scalar(value: &str) -> Value {
+    match value.parse::<u16>() {
+        Ok(number) => Value::from(number),
+        Err(_) => Value::from(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host<'a>(hosts: &'a [InventoryHost], name: &str) -> &'a InventoryHost {
+        hosts.iter().find(|host| host.name == name).unwrap()
+    }
+
+    #[test]
+    fn parse_ini_inventory() {
+        let hosts = parse_inventory(
+            Path::new("hosts"),
+            "jump.example.com\n\
+             [web]\n\
+             web[01:02].example.com ansible_user=deploy\n\
+             [db]\n\
+             db01 ansible_host=10.0.2.1 ansible_ssh_common_args='-o ProxyJump=jump'\n\
+             [prod:children]\n\
+             db\n\
+             [prod:vars]\n\
+             ansible_user=admin\n\
+             ansible_port=2222\n\
+             [db:vars]\n\
+             ansible_port=2200\n",
+        )
+        .unwrap();
+        let names = hosts.iter().map(|host| host.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, ["jump.example.com", "web01.example.com", "web02.example.com", "db01"]);
+        assert!(host(&hosts, "jump.example.com").groups.is_empty());
+        assert_eq!(host(&hosts, "web02.example.com").var(&["ansible_user"]), Some("deploy"));
+        let db = host(&hosts, "db01");
+        assert_eq!(db.groups, ["prod", "db"]);
+        // the variables of a group override the ones of the groups it is in
+        assert_eq!(db.var(&["ansible_port"]), Some("2200"));
+        assert_eq!(db.var(&["ansible_user"]), Some("admin"));
+        assert_eq!(db.var(&["ansible_ssh_common_args"]), Some("-o ProxyJump=jump"));
+    }
+
+    #[test]
+    fn parse_yaml_inventory() {
+        let hosts = parse_inventory(
+            Path::new("inventory.yml"),
+            "all:
+  vars:
+    ansible_user: root
+  children:
+    web:
+      hosts:
+        web[1:3:2]:
+          ansible_port: 2222
+      vars:
+        ansible_user: deploy
+    db:
+      hosts:
+        db1:
+",
+        )
+        .unwrap();
+        let names = hosts.iter().map(|host| host.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, ["web1", "web3", "db1"]);
+        assert_eq!(host(&hosts, "web3").var(&["ansible_port"]), Some("2222"));
+        assert_eq!(host(&hosts, "web3").var(&["ansible_user"]), Some("deploy"));
+        assert_eq!(host(&hosts, "db1").var(&["ansible_user"]), Some("root"));
+        assert_eq!(host(&hosts, "db1").groups, ["db"]);
+    }
+
+    #[test]
+    fn split_words_with_quotes() {
+        assert_eq!(
+            split_words(r#"-o "ServerAliveInterval 30" -J 'a b' c\ d"#),
+            ["-o", "ServerAliveInterval 30", "-J", "a b", "c d"]
+        );
+        assert_eq!(split_words("''"), [""]);
+    }
+
+    #[test]
+    fn host_options_from_the_variables() {
+        let hosts = parse_inventory(
+            Path::new("hosts.ini"),
+            "db ansible_host=10.0.0.5 ansible_port=2200 ansible_ssh_common_args='-o ForwardAgent=yes -J jump -F cfg'\n",
+        )
+        .unwrap();
+        let (options, warnings) = host_options(&hosts[0]);
+        let options = options.iter().map(|(key, value)| format!("{key} {value}")).collect::<Vec<String>>();
+        assert_eq!(options, ["HostName 10.0.0.5", "port 2200", "ForwardAgent yes", "ProxyJump jump"]);
+        assert_eq!(warnings, ["\"-F cfg\" of ansible_ssh_common_args has no equivalent in ssh_config"]);
+    }
+
+    fn exported_hosts() -> Vec<InventoryHost> {
+        let ssh_hosts = crate::ssh_config::parse_ssh_hosts(
+            "Host web1\n    HostName 10.0.0.1\n    User deploy\n\n\
+             Host db1\n    HostName 10.0.0.2\n    Port 2222\n    ProxyCommand nc -x proxy:1080 10.0.0.2 22\n\n\
+             Host jump\n    HostName jump.example.com\n\n\
+             Host *\n    ProxyJump none\n",
+        );
+        vec![
+            inventory_host("jump", "", &ssh_hosts),
+            inventory_host("web1", "web, prod", &ssh_hosts),
+            inventory_host("db1", "db,prod", &ssh_hosts),
+        ]
+    }
+
+    #[test]
+    fn inventory_host_from_the_options() {
+        let hosts = exported_hosts();
+        let db = host(&hosts, "db1");
+        assert_eq!(db.groups, ["db", "prod"]);
+        assert_eq!(db.var(&["ansible_port"]), Some("2222"));
+        assert_eq!(
+            db.var(&["ansible_ssh_common_args"]),
+            Some("-o ProxyCommand=\"nc -x proxy:1080 10.0.0.2 22\"")
+        );
+        assert_eq!(host(&hosts, "jump").var(&["ansible_ssh_common_args"]), None);
+    }
+
+    #[test]
+    fn write_ini_inventory() {
+        let mut hosts = exported_hosts();
+        hosts[0].vars.insert(String::from("ansible_ssh_private_key_file"), String::from("~/keys/o'neil key"));
+        assert_eq!(
+            inventory_ini(&hosts),
+            "jump ansible_host=jump.example.com ansible_ssh_private_key_file=\"~/keys/o'neil key\"\n\
+             \n\
+             [web]\n\
+             web1 ansible_host=10.0.0.1 ansible_user=deploy\n\
+             \n\
+             [prod]\n\
+             web1\n\
+             db1\n\
+             \n\
+             [db]\n\
+             db1 ansible_host=10.0.0.2 ansible_port=2222 \
+             ansible_ssh_common_args='-o ProxyCommand=\"nc -x proxy:1080 10.0.0.2 22\"'\n"
+        );
+    }
+
+    #[test]
+    fn write_yaml_inventory() {
+        let inventory = inventory_yaml(&exported_hosts()).unwrap();
+        assert_eq!(
+            inventory,
+            "all:
+  hosts:
+    jump:
+      ansible_host: jump.example.com
+  children:
+    web:
+      hosts:
+        web1:
+          ansible_host: 10.0.0.1
+          ansible_user: deploy
+    prod:
+      hosts:
+        web1: null
+        db1: null
+    db:
+      hosts:
+        db1:
+          ansible_host: 10.0.0.2
+          ansible_port: 2222
+          ansible_ssh_common_args: -o ProxyCommand=\"nc -x proxy:1080 10.0.0.2 22\"
+"
+        );
+        // the exported inventory reads back with the variables of every host
+        let hosts = parse_inventory(Path::new("inventory.yml"), inventory.as_str()).unwrap();
+        assert_eq!(host(&hosts, "db1").groups, ["db", "prod"]);
+        assert_eq!(host(&hosts, "db1").var(&["ansible_port"]), Some("2222"));
+        assert_eq!(host(&hosts, "web1").var(&["ansible_user"]), Some("deploy"));
+    }
+}
@@ -0,0 +1,365 @@
+use crate::error::CommandError;
+use portable_pty::{native_pty_system, Child, CommandBuilder, PtySize};
+use std::error::Error;
+use std::io::{BufRead, Read, Write};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use std::{io, thread};
+
+const COLORS: [u8; 6] = [32, 33, 34, 35, 36, 31];
+/// time without output from a host after which the text without a new line is printed
+const IDLE_OUTPUT: Duration = Duration::from_millis(100);
+
+struct Session {
+    host: String,
+    prefix: String,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    muted: bool,
+    closed: bool,
+}
+
+enum Event {
+    Input(Option<String>),
+    Closed(usize),
+}
+
+/// opens an interactive session to every host and sends every typed line to all the sessions that are not muted
+pub fn broadcast_to_hosts(
+    hosts: Vec<String>,
+    command: &str,
+    args: &Option<Vec<String>>,
+) -> Result<(), Box<dyn Error>> {
+    if hosts.is_empty() {
+        return Err(Box::new(CommandError::new("Broadcast", "no connection matches the filter")));
+    }
+    let (sender, receiver) = channel();
+    let width = hosts.iter().map(|host| host.len()).max().unwrap_or(0);
+    let mut sessions = vec![];
+    for (index, host) in hosts.into_iter().enumerate() {
+        let prefix = format!("\x1b[{}m[{:width$}]\x1b[0m", COLORS[index % COLORS.len()], host);
+        sessions.push(open_session(index, host, prefix, command, args, sender.clone())?);
+    }
+    let stdin_sender = sender.clone();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if stdin_sender.send(Event::Input(Some(line))).is_err() {
+                return;
+            }
+        }
+        let _ = stdin_sender.send(Event::Input(None));
+    });
+    println!(
+        "Broadcasting to {} hosts, type \":help\" to list the available commands",
+        sessions.len()
+    );
+    while let Ok(event) = receiver.recv() {
+        match event {
+            Event::Input(None) => break,
+            Event::Input(Some(line)) => {
+                if !handle_input(&mut sessions, line.as_str()) {
+                    break;
+                }
+            }
+            Event::Closed(index) => {
+                let session = &mut sessions[index];
+                session.closed = true;
+                let status = session
+                    .child
+                    .wait()
+                    .map(|status| status.exit_code().to_string())
+                    .unwrap_or_else(|e| e.to_string());
+                println!("{} session closed ({})", session.prefix, status);
+                if sessions.iter().all(|session| session.closed) {
+                    break;
+                }
+            }
+        }
+    }
+    for session in sessions.iter_mut().filter(|session| !session.closed) {
+        let _ = session.child.kill();
+        let _ = session.child.wait();
+    }
+    Ok(())
+}
+
+fn open_session(
+    index: usize,
+    host: String,
+    prefix: String,
+    command: &str,
+    args: &Option<Vec<String>>,
+    sender: Sender<Event>,
+) -> Result<Session, Box<dyn Error>> {
+    let pair = native_pty_system().openpty(PtySize::default())?;
+    let mut command = CommandBuilder::new(command);
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command.arg(host.as_str());
+    let child = pair.slave.spawn_command(command)?;
+    drop(pair.slave);
+    let reader = pair.master.try_clone_reader()?;
+    let writer = pair.master.take_writer()?;
+    let output_prefix = prefix.clone();
+    thread::spawn(move || {
+        print_output(reader, output_prefix.as_str());
+        let _ = sender.send(Event::Closed(index));
+    });
+    Ok(Session {
+        host,
+        prefix,
+        writer,
+        child,
+        muted: false,
+        closed: false,
+    })
+}
+
+fn print_output(mut reader: Box<dyn Read + Send>, prefix: &str) {
+    let (sender, receiver) = channel::<Vec<u8>>();
+    thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        while let Ok(read @ 1..) = reader.read(&mut buffer) {
+            if sender.send(buffer[..read].to_vec()).is_err() {
+                return;
+            }
+        }
+    });
+    print_chunks(receiver, prefix, IDLE_OUTPUT, &mut io::stdout());
+}
+
+/// prints the output read from a host until it is closed, prefixing every line with the host
+fn print_chunks(receiver: Receiver<Vec<u8>>, prefix: &str, idle: Duration, out: &mut impl Write) {
+    // the lines can arrive split in several reads, only whole lines are printed while the host is writing
+    let mut pending: Vec<u8> = vec![];
+    loop {
+        match receiver.recv_timeout(idle) {
+            Ok(chunk) => {
+                pending.extend(chunk);
+                while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+                    print_line(out, prefix, &pending.drain(..=end).collect::<Vec<u8>>());
+                }
+            }
+            // prompts do not end with a new line, so they are shown when the host stops writing
+            Err(RecvTimeoutError::Timeout) => {
+                if !pending.is_empty() {
+                    print_line(out, prefix, &pending);
+                    pending.clear();
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    if !pending.is_empty() {
+        print_line(out, prefix, &pending);
+    }
+}
+
+fn print_line(out: &mut impl Write, prefix: &str, line: &[u8]) {
+    let _ = writeln!(out, "{} {}", prefix, String::from_utf8_lossy(line).trim_end_matches(['\r', '\n']));
+}
+
+fn send_line(sessions: &mut [Session], line: &str) {
+    for session in sessions.iter_mut().filter(|session| !session.muted && !session.closed) {
+        if let Err(e) = writeln!(session.writer, "{line}").and_then(|_| session.writer.flush()) {
+            eprintln!("{} couldn't send the line: {}", session.prefix, e);
+        }
+    }
+}
+
+/// sends the typed line to the hosts or runs it as a control command when it starts with ":", returns false when
+/// the broadcast should finish
+fn handle_input(sessions: &mut [Session], line: &str) -> bool {
+    if let Some(line) = line.strip_prefix("::") {
+        send_line(sessions, format!(":{line}").as_str());
+    } else if let Some(control) = line.strip_prefix(':') {
+        return run_control(sessions, control);
+    } else {
+        send_line(sessions, line);
+    }
+    true
+}
+
+/// runs a broadcast control command, returns false when the broadcast should finish
+fn run_control(sessions: &mut [Session], control: &str) -> bool {
+    let mut words = control.split_whitespace();
+    match words.next() {
+        Some("mute") | Some("unmute") => {
+            let muted = control.trim_start().starts_with("mute");
+            for host in words {
+                match sessions.iter_mut().find(|session| session.host == host) {
+                    Some(session) => session.muted = muted,
+                    None => eprintln!("no session for the host {host}"),
+                }
+            }
+            print_sessions(sessions);
+        }
+        Some("hosts") => print_sessions(sessions),
+        Some("quit") => return false,
+        _ => {
+            println!(":hosts                 lists the sessions and their state");
+            println!(":mute <Host>...        stops sending the typed lines to the hosts");
+            println!(":unmute <Host>...      sends the typed lines to the hosts again");
+            println!(":quit                  closes every session");
+            println!("::<text>               sends \":<text>\" to the hosts");
+        }
+    }
+    true
+}
+
+fn print_sessions(sessions: &[Session]) {
+    for session in sessions {
+        let state = if session.closed {
+            "closed"
+        } else if session.muted {
+            "muted"
+        } else {
+            "active"
+        };
+        println!("{} {}", session.prefix, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use portable_pty::{ChildKiller, ExitStatus};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug)]
+    struct StubChild;
+
+    impl ChildKiller for StubChild {
+        fn kill(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+            Box::new(StubChild)
+        }
+    }
+
+    impl Child for StubChild {
+        fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+            Ok(None)
+        }
+
+        fn wait(&mut self) -> io::Result<ExitStatus> {
+            Ok(ExitStatus::with_exit_code(0))
+        }
+
+        fn process_id(&self) -> Option<u32> {
+            None
+        }
+
+        #[cfg(windows)]
+        fn as_raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+            None
+        }
+    }
+
+    /// writer that keeps what the broadcast sends to a host
+    #[derive(Clone, Default)]
+    struct Sent(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Sent {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Sent {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn sessions(hosts: &[&str]) -> (Vec<Session>, Vec<Sent>) {
+        let sent = hosts.iter().map(|_| Sent::default()).collect::<Vec<Sent>>();
+        let sessions = hosts
+            .iter()
+            .zip(sent.iter())
+            .map(|(host, sent)| Session {
+                host: host.to_string(),
+                prefix: format!("[{host}]"),
+                writer: Box::new(sent.clone()),
+                child: Box::new(StubChild),
+                muted: false,
+                closed: false,
+            })
+            .collect();
+        (sessions, sent)
+    }
+
+    #[test]
+    fn mute_and_unmute_hosts() {
+        let (mut sessions, sent) = sessions(&["web1", "web2", "db"]);
+        assert!(handle_input(&mut sessions, ":mute web2 db"));
+        assert!(handle_input(&mut sessions, "uptime"));
+        assert!(handle_input(&mut sessions, ":unmute db"));
+        assert!(handle_input(&mut sessions, "whoami"));
+        assert!(handle_input(&mut sessions, ":hosts"));
+        assert_eq!(sessions.iter().map(|session| session.muted).collect::<Vec<bool>>(), [false, true, false]);
+        assert_eq!(sent[0].text(), "uptime\nwhoami\n");
+        assert_eq!(sent[1].text(), "");
+        assert_eq!(sent[2].text(), "whoami\n");
+    }
+
+    #[test]
+    fn closed_sessions_get_no_lines() {
+        let (mut sessions, sent) = sessions(&["web1", "web2"]);
+        sessions[1].closed = true;
+        assert!(handle_input(&mut sessions, "uptime"));
+        assert_eq!(sent[0].text(), "uptime\n");
+        assert_eq!(sent[1].text(), "");
+    }
+
+    #[test]
+    fn double_colon_sends_a_literal_colon() {
+        let (mut sessions, sent) = sessions(&["web1"]);
+        assert!(handle_input(&mut sessions, "::quit"));
+        assert_eq!(sent[0].text(), ":quit\n");
+    }
+
+    #[test]
+    fn quit_finishes_the_broadcast() {
+        let (mut sessions, sent) = sessions(&["web1"]);
+        assert!(!handle_input(&mut sessions, ":quit"));
+        assert!(handle_input(&mut sessions, ":help"));
+        assert_eq!(sent[0].text(), "");
+    }
+
+    #[test]
+    fn output_is_printed_by_lines() {
+        let (sender, receiver) = channel();
+        for chunk in ["up", "time: 10 days\r\nload", ": 0.1\n"] {
+            sender.send(chunk.as_bytes().to_vec()).unwrap();
+        }
+        drop(sender);
+        let mut out = vec![];
+        print_chunks(receiver, "[web1]", Duration::from_secs(60), &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "[web1] uptime: 10 days\n[web1] load: 0.1\n");
+    }
+
+    #[test]
+    fn partial_lines_are_printed_when_the_host_goes_idle() {
+        let (sender, receiver) = channel();
+        // the chunks sent before the host goes idle are already queued when the output is read
+        sender.send(b"motd\n[user@web1 ~]$ ".to_vec()).unwrap();
+        let printer = thread::spawn(move || {
+            let mut out = vec![];
+            print_chunks(receiver, "[web1]", Duration::from_millis(50), &mut out);
+            out
+        });
+        thread::sleep(Duration::from_millis(300));
+        sender.send(b"ls\nfile".to_vec()).unwrap();
+        drop(sender);
+        let out = String::from_utf8(printer.join().unwrap()).unwrap();
+        assert_eq!(out, "[web1] motd\n[web1] [user@web1 ~]$ \n[web1] ls\n[web1] file\n");
+    }
+}
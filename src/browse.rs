@@ -0,0 +1,328 @@
+use crate::copy::sftp_quote;
+use crate::error::CommandError;
+use prettytable::{color, Attr, Cell, Row, Table};
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Entry of a remote directory as listed by "ls -la"
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    directory: bool,
+    /// symbolic links may point to a directory, "ls" doesn't tell
+    link: bool,
+    size: String,
+    modified: String,
+}
+
+/// Remote host browsed with one sftp batch session per operation
+struct Browser<'a> {
+    command: &'a str,
+    connection_name: &'a str,
+    args: &'a Option<Vec<String>>,
+    path: String,
+    entries: Vec<Entry>,
+}
+
+pub fn sftp_command(command: &str, connection_name: &str, args: &Option<Vec<String>>) -> Command {
+    // the Host line may have several names, sftp connects to the first one
+    let connection_name = connection_name.split_whitespace().next().unwrap_or_default();
+    let mut command = Command::new(command);
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command
+        .arg(connection_name)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    command
+}
+
+/// lists the remote directories and reads the commands to navigate them and copy files from the terminal
+pub fn browse(command: &str, connection_name: &str, args: &Option<Vec<String>>) -> Result<(), Box<dyn Error>> {
+    let connection_name = connection_name.split_whitespace().next().unwrap_or_default();
+    let mut browser = Browser {
+        command,
+        connection_name,
+        args,
+        path: String::new(),
+        entries: vec![],
+    };
+    let output = browser.batch("pwd\n")?;
+    browser.path = output
+        .lines()
+        .find_map(|line| line.strip_prefix("Remote working directory: "))
+        .ok_or_else(|| CommandError::new("Browse", "couldn't read the remote working directory"))?
+        .to_string();
+    browser.list()?;
+    println!("type \"help\" to list the available commands");
+    loop {
+        print!("{}:{}> ", connection_name, browser.path);
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        let line = line.trim();
+        let (action, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        let result = match action {
+            "" | "ls" => browser.list(),
+            "cd" => browser.change_directory(argument),
+            ".." => browser.change_directory(".."),
+            "get" => browser.get(argument),
+            "put" => browser.put(argument),
+            "quit" | "q" | "exit" => return Ok(()),
+            "help" | "?" => {
+                print_help();
+                Ok(())
+            }
+            _ if action.parse::<usize>().is_ok() => browser.change_directory(action),
+            _ => {
+                eprintln!("unknown command {action}, type \"help\" to list the available commands");
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("{e}");
+        }
+    }
+}
+
+fn print_help() {
+    println!("ls                        lists the current directory again");
+    println!("<Index> | cd <Index|Path> enters the directory");
+    println!("..                        goes to the parent directory");
+    println!("get <Index|Name> [Local]  downloads the file or directory to the local path, the current directory by default");
+    println!("put <Local>               uploads the local file or directory to the current remote directory");
+    println!("quit                      exits the browser");
+}
+
+impl Browser<'_> {
+    /// runs the commands in a new sftp session and returns its output, the session stops at the first failed command
+    fn batch(&self, commands: &str) -> Result<String, Box<dyn Error>> {
+        let mut command = Command::new(self.command);
+        command.arg("-b").arg("-");
+        if let Some(args) = self.args {
+            command.args(args.iter());
+        }
+        let mut child = command
+            .arg(self.connection_name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(commands.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.lines().last().unwrap_or("sftp failed").to_string();
+            return Err(Box::new(CommandError::new("Browse", message.as_str())));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn list(&mut self) -> Result<(), Box<dyn Error>> {
+        // "cd" fails for the links that don't point to a directory, "ls" would list the link itself
+        let output = self.batch(format!("cd {}\nls -la\n", sftp_quote(self.path.as_str())).as_str())?;
+        let mut entries = output
+            .lines()
+            .filter(|line| !line.starts_with("sftp>"))
+            .filter_map(parse_entry)
+            .filter(|entry| entry.name != "." && entry.name != "..")
+            .collect::<Vec<Entry>>();
+        entries.sort_by(|a, b| b.directory.cmp(&a.directory).then(a.name.cmp(&b.name)));
+        self.entries = entries;
+        let mut table = Table::new();
+        table.add_row(Row::new(
+            ["Index", "Name", "Size", "Modified"]
+                .iter()
+                .map(|title| {
+                    Cell::new(title)
+                        .with_style(Attr::Bold)
+                        .with_style(Attr::ForegroundColor(color::GREEN))
+                })
+                .collect::<Vec<Cell>>(),
+        ));
+        for (index, entry) in self.entries.iter().enumerate() {
+            let (name, color) = match (entry.directory, entry.link) {
+                (true, _) => (format!("{}/", entry.name), color::BLUE),
+                (false, true) => (format!("{}@", entry.name), color::CYAN),
+                (false, false) => (entry.name.clone(), color::CYAN),
+            };
+            table.add_row(Row::new(
+                [index.to_string(), name, entry.size.clone(), entry.modified.clone()]
+                    .iter()
+                    .map(|cell| Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(color)))
+                    .collect::<Vec<Cell>>(),
+            ));
+        }
+        table.printstd();
+        Ok(())
+    }
+
+    /// returns the entry of the current listing with the index or the name
+    fn entry(&self, selection: &str) -> Option<&Entry> {
+        match selection.parse::<usize>() {
+            Ok(index) => self.entries.get(index),
+            Err(_) => self.entries.iter().find(|entry| entry.name == selection),
+        }
+    }
+
+    fn change_directory(&mut self, selection: &str) -> Result<(), Box<dyn Error>> {
+        let path = match self.entry(selection) {
+            Some(entry) if !entry.directory && !entry.link => {
+                return Err(Box::new(CommandError::new("Browse", format!("{} is not a directory", entry.name).as_str())));
+            }
+            Some(entry) => join_remote(self.path.as_str(), entry.name.as_str()),
+            None if selection.is_empty() => return Ok(()),
+            None => join_remote(self.path.as_str(), selection),
+        };
+        let previous = std::mem::replace(&mut self.path, path);
+        if let Err(e) = self.list() {
+            self.path = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn get(&self, argument: &str) -> Result<(), Box<dyn Error>> {
+        let (selection, local) = split_argument(argument);
+        let entry = self
+            .entry(selection)
+            .ok_or_else(|| CommandError::new("Browse", format!("no file {selection} in the directory").as_str()))?;
+        let local = if local.is_empty() { "." } else { local };
+        let flag = if entry.directory { " -r" } else { "" };
+        let remote = join_remote(self.path.as_str(), entry.name.as_str());
+        self.batch(format!("get{} {} {}\n", flag, sftp_quote(remote.as_str()), sftp_quote(local)).as_str())?;
+        println!("downloaded {} to {}", remote, local);
+        Ok(())
+    }
+
+    fn put(&mut self, local: &str) -> Result<(), Box<dyn Error>> {
+        if local.is_empty() {
+            return Err(Box::new(CommandError::new("Browse", "no local file to upload")));
+        }
+        let flag = if Path::new(local).is_dir() { " -r" } else { "" };
+        self.batch(format!("put{} {} {}\n", flag, sftp_quote(local), sftp_quote(self.path.as_str())).as_str())?;
+        println!("uploaded {} to {}", local, self.path);
+        self.list()
+    }
+}
+
+/// splits "<Index|Name> [Local]", the name can be quoted when it has spaces
+fn split_argument(argument: &str) -> (&str, &str) {
+    if let Some(quoted) = argument.strip_prefix('"') {
+        if let Some((name, rest)) = quoted.split_once('"') {
+            return (name, rest.trim());
+        }
+    }
+    match argument.split_once(char::is_whitespace) {
+        Some((name, rest)) => (name, rest.trim()),
+        None => (argument, ""),
+    }
+}
+
+/// parses a line of "ls -la" like "drwxr-xr-x 2 user group 4096 Oct 1 10:00 name", the name may have spaces
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut rest = line.trim_start();
+    let mut fields = vec![];
+    for _ in 0..8 {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    if rest.is_empty() || !matches!(fields[0].chars().next(), Some('d' | '-' | 'l')) {
+        return None;
+    }
+    let name = match fields[0].starts_with('l') {
+        true => rest.split(" -> ").next().unwrap_or(rest),
+        false => rest,
+    };
+    Some(Entry {
+        name: name.to_string(),
+        directory: fields[0].starts_with('d'),
+        link: fields[0].starts_with('l'),
+        size: fields[4].to_string(),
+        modified: fields[5..8].join(" "),
+    })
+}
+
+/// joins a remote path resolving "..", an absolute path replaces the current one
+fn join_remote(base: &str, path: &str) -> String {
+    let mut segments: Vec<&str> = match path.starts_with('/') {
+        true => vec![],
+        false => base.split('/').filter(|segment| !segment.is_empty()).collect(),
+    };
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, directory: bool, link: bool) -> Entry {
+        Entry {
+            name: name.to_string(),
+            directory,
+            link,
+            size: String::from("4096"),
+            modified: String::from("Oct 2 09:12"),
+        }
+    }
+
+    #[test]
+    fn parse_entries_of_ls() {
+        assert_eq!(parse_entry("drwxr-xr-x    2 centos   centos       4096 Oct 2 09:12 logs"), Some(entry("logs", true, false)));
+        assert_eq!(
+            parse_entry("-rw-r--r--    1 centos   centos       4096 Oct 2 09:12 release notes.txt"),
+            Some(entry("release notes.txt", false, false))
+        );
+        assert_eq!(
+            parse_entry("lrwxrwxrwx    1 centos   centos       4096 Oct 2 09:12 current -> /opt/app/v2"),
+            Some(entry("current", false, true))
+        );
+        assert_eq!(parse_entry("sftp> ls -la"), None);
+        assert_eq!(parse_entry("total 8"), None);
+        assert_eq!(parse_entry("srwxr-xr-x    1 centos   centos          0 Oct 2 09:12 agent.sock"), None);
+    }
+
+    #[test]
+    fn join_remote_paths() {
+        assert_eq!(join_remote("/home/centos", "logs"), "/home/centos/logs");
+        assert_eq!(join_remote("/home/centos", "../root/./.ssh/"), "/home/root/.ssh");
+        assert_eq!(join_remote("/home/centos", "/var/log"), "/var/log");
+        assert_eq!(join_remote("/", ".."), "/");
+        assert_eq!(join_remote("/home", ""), "/home");
+    }
+
+    #[test]
+    fn sftp_connects_to_the_first_alias() {
+        let args = Some(vec![String::from("-P"), String::from("2222")]);
+        let command = sftp_command("sftp", "web web.prod", &args);
+        assert_eq!(command.get_program(), "sftp");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["-P", "2222", "web"]);
+    }
+
+    #[test]
+    fn split_quoted_arguments() {
+        assert_eq!(split_argument("1 ./logs"), ("1", "./logs"));
+        assert_eq!(split_argument("\"release notes.txt\"  /tmp"), ("release notes.txt", "/tmp"));
+        assert_eq!(split_argument("app.py"), ("app.py", ""));
+    }
+}
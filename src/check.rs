@@ -0,0 +1,180 @@
+use crate::exec::run_parallel;
+use crate::ssh_config::{effective_address, effective_option, is_pattern, split_destination, SshHost};
+use crate::OutputFormat;
+use prettytable::{color, Attr, Cell, Row, Table};
+use serde::Serialize;
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// Result of checking if a host is reachable
+#[derive(Serialize)]
+pub struct CheckResult {
+    pub index: String,
+    pub host: String,
+    pub host_name: String,
+    pub user: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+    pub reachable: bool,
+    pub latency_ms: Option<u128>,
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// checks every row of the table connecting to the effective address of the host and reading the ssh banner
+pub fn check_hosts(
+    rows: Vec<Vec<String>>,
+    ssh_hosts: &[SshHost],
+    proxy_jump: bool,
+    timeout: Duration,
+    jobs: usize,
+    format: &OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let rows = rows
+        .into_iter()
+        .filter(|row| !is_pattern(row[1].as_str()))
+        .collect();
+    let results = run_parallel(rows, jobs, |row: Vec<String>| {
+        let (address, via) = check_address(ssh_hosts, row[1].as_str(), proxy_jump);
+        let mut result = CheckResult {
+            index: row[0].clone(),
+            host: row[1].clone(),
+            host_name: row[2].clone(),
+            user: row[3].clone(),
+            address: format!("{}:{}", address.0, address.1),
+            via,
+            reachable: false,
+            latency_ms: None,
+            version: None,
+            error: None,
+        };
+        match check_address_reachable(&address, timeout) {
+            Ok((latency, version)) => {
+                result.reachable = true;
+                result.latency_ms = Some(latency.as_millis());
+                result.version = version;
+            }
+            Err(e) => result.error = Some(e.to_string()),
+        }
+        result
+    });
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Table => print_results(&results),
+    }
+    Ok(())
+}
+
+/// returns the address to connect to, when following ProxyJump it is the first jump host of the chain
+fn check_address(ssh_hosts: &[SshHost], host: &str, proxy_jump: bool) -> ((String, u16), Option<String>) {
+    // ssh resolves the options of the name it connects to, the first alias of the Host line
+    let alias = host.split_whitespace().next().unwrap_or_default();
+    let jump = effective_option(ssh_hosts, alias, "ProxyJump")
+        .filter(|jump| proxy_jump && !jump.eq_ignore_ascii_case("none"));
+    match jump {
+        Some(jump) => {
+            let first_jump = jump.split(',').next().unwrap_or_default().trim().to_string();
+            let (_, jump_host, jump_port) = split_destination(first_jump.as_str());
+            let (host_name, port) = effective_address(ssh_hosts, jump_host.as_str());
+            ((host_name, jump_port.unwrap_or(port)), Some(first_jump))
+        }
+        None => (effective_address(ssh_hosts, alias), None),
+    }
+}
+
+fn check_address_reachable(address: &(String, u16), timeout: Duration) -> Result<(Duration, Option<String>), Box<dyn Error>> {
+    let mut last_error: Box<dyn Error> = format!("couldn't resolve {}", address.0).into();
+    for socket_address in (address.0.as_str(), address.1).to_socket_addrs()? {
+        let start = Instant::now();
+        match TcpStream::connect_timeout(&socket_address, timeout) {
+            Ok(stream) => {
+                let latency = start.elapsed();
+                stream.set_read_timeout(Some(timeout))?;
+                // the server may send other lines before the identification string
+                let version = BufReader::new(stream)
+                    .lines()
+                    .take(10)
+                    .map_while(Result::ok)
+                    .find(|line| line.starts_with("SSH-"))
+                    .map(|line| line.trim().to_string());
+                return Ok((latency, version));
+            }
+            Err(e) => last_error = Box::new(e),
+        }
+    }
+    Err(last_error)
+}
+
+fn print_results(results: &[CheckResult]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Index", "HostName", "Host", "User", "Address", "Status", "Latency", "Version"]
+            .iter()
+            .map(|title| {
+                Cell::new(title)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::GREEN))
+            })
+            .collect::<Vec<Cell>>(),
+    ));
+    for result in results {
+        let address = match &result.via {
+            Some(via) => format!("{} (via {})", result.address, via),
+            None => result.address.clone(),
+        };
+        let status = match &result.error {
+            Some(error) => format!("unreachable: {error}"),
+            None => String::from("reachable"),
+        };
+        let status_color = if result.reachable { color::GREEN } else { color::RED };
+        let mut cells = [
+            result.index.clone(),
+            result.host.clone(),
+            result.host_name.clone(),
+            result.user.clone(),
+            address,
+        ]
+            .iter()
+            .map(|cell| Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(color::CYAN)))
+            .collect::<Vec<Cell>>();
+        cells.push(Cell::new(status.as_str()).with_style(Attr::ForegroundColor(status_color)));
+        cells.push(
+            Cell::new(
+                result
+                    .latency_ms
+                    .map(|latency| format!("{latency}ms"))
+                    .unwrap_or_default()
+                    .as_str(),
+            )
+                .with_style(Attr::ForegroundColor(color::CYAN)),
+        );
+        cells.push(
+            Cell::new(result.version.clone().unwrap_or_default().as_str())
+                .with_style(Attr::ForegroundColor(color::CYAN)),
+        );
+        table.add_row(Row::new(cells));
+    }
+    table.printstd();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh_config::parse_ssh_hosts;
+
+    #[test]
+    fn check_address_of_the_first_alias() {
+        let hosts = parse_ssh_hosts(
+            "Host web web.prod\n    HostName 10.0.0.1\n    Port 2222\n    ProxyJump admin@bastion:2200\nHost bastion\n    HostName 10.0.0.2\n",
+        );
+        assert_eq!(check_address(&hosts, "web web.prod", false), ((String::from("10.0.0.1"), 2222), None));
+        assert_eq!(
+            check_address(&hosts, "web web.prod", true),
+            ((String::from("10.0.0.2"), 2200), Some(String::from("admin@bastion:2200")))
+        );
+    }
+}
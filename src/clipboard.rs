@@ -0,0 +1,120 @@
+use arboard::Clipboard;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::ValueEnum;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{stdout, IsTerminal, Write};
+
+/// Where the exported text is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportTarget {
+    /// the system clipboard, falling back to osc52 or stdout when there is none
+    Clipboard,
+    /// the clipboard of the terminal using the OSC 52 escape sequence, works over ssh
+    Osc52,
+    Stdout,
+}
+
+impl Display for ExportTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportTarget::Clipboard => write!(f, "clipboard"),
+            ExportTarget::Osc52 => write!(f, "terminal clipboard"),
+            ExportTarget::Stdout => write!(f, "standard output"),
+        }
+    }
+}
+
+/// sends the text to the target, returns the target that was finally used
+pub fn export_text(text: &str, target: ExportTarget) -> Result<ExportTarget, Box<dyn Error>> {
+    match target {
+        ExportTarget::Clipboard => match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+            Ok(()) => Ok(ExportTarget::Clipboard),
+            Err(e) => {
+                let fallback = fallback(stdout().is_terminal());
+                eprintln!("the system clipboard is not available ({e}), using the {fallback}");
+                export_text(text, fallback)
+            }
+        },
+        ExportTarget::Osc52 => {
+            let mut stdout = stdout().lock();
+            write!(stdout, "{}", osc52(text))?;
+            stdout.flush()?;
+            Ok(ExportTarget::Osc52)
+        }
+        ExportTarget::Stdout => {
+            println!("{text}");
+            Ok(ExportTarget::Stdout)
+        }
+    }
+}
+
+/// target used when the system clipboard fails, osc52 only reaches a terminal
+fn fallback(terminal: bool) -> ExportTarget {
+    match terminal {
+        true => ExportTarget::Osc52,
+        false => ExportTarget::Stdout,
+    }
+}
+
+/// Terminal multiplexer that has to pass the escape sequences through to the outer terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    fn detect() -> Option<Multiplexer> {
+        if std::env::var_os("TMUX").is_some() {
+            Some(Multiplexer::Tmux)
+        } else if std::env::var("TERM").is_ok_and(|term| term.starts_with("screen")) {
+            Some(Multiplexer::Screen)
+        } else {
+            None
+        }
+    }
+}
+
+/// escape sequence that asks the terminal to put the text in its clipboard, wrapped for tmux and screen
+fn osc52(text: &str) -> String {
+    osc52_sequence(text, Multiplexer::detect())
+}
+
+fn osc52_sequence(text: &str, multiplexer: Option<Multiplexer>) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    match multiplexer {
+        Some(Multiplexer::Tmux) => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+        Some(Multiplexer::Screen) => format!("\x1bP{}\x1b\\", sequence),
+        None => sequence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_encodes_the_text() {
+        assert_eq!(osc52_sequence("ssh web", None), "\x1b]52;c;c3NoIHdlYg==\x07");
+    }
+
+    #[test]
+    fn osc52_passes_through_multiplexers() {
+        assert_eq!(
+            osc52_sequence("ssh web", Some(Multiplexer::Tmux)),
+            "\x1bPtmux;\x1b\x1b]52;c;c3NoIHdlYg==\x07\x1b\\"
+        );
+        assert_eq!(
+            osc52_sequence("ssh web", Some(Multiplexer::Screen)),
+            "\x1bP\x1b]52;c;c3NoIHdlYg==\x07\x1b\\"
+        );
+    }
+
+    #[test]
+    fn clipboard_falls_back_to_osc52_only_on_a_terminal() {
+        assert_eq!(fallback(true), ExportTarget::Osc52);
+        assert_eq!(fallback(false), ExportTarget::Stdout);
+    }
+}
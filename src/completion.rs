@@ -0,0 +1,202 @@
+use crate::metadata::Metadata;
+use crate::tags::split_tags;
+use crate::{add_pins, read_ssh_config_file, sort_rows, SortOrder};
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Powershell, Zsh};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// environment variable the shell sets to ask ssh-view for the completions of the command line
+pub const COMPLETE_VAR: &str = "SSH_VIEW_COMPLETE";
+
+/// Shell to write the completion script for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Powershell,
+}
+
+impl CompletionShell {
+    fn completer(&self) -> &'static dyn EnvCompleter {
+        match self {
+            CompletionShell::Bash => &Bash,
+            CompletionShell::Zsh => &Zsh,
+            CompletionShell::Fish => &Fish,
+            CompletionShell::Elvish => &Elvish,
+            CompletionShell::Powershell => &Powershell,
+        }
+    }
+}
+
+/// writes the script that registers the completions in the shell, the script calls ssh-view back for every completion
+/// so the hosts are always the ones in the config file
+pub fn write_registration(shell: CompletionShell, buf: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    let completer = env::current_exe()
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or(String::from("ssh-view"));
+    shell
+        .completer()
+        .write_registration(COMPLETE_VAR, "ssh_view", "ssh-view", completer.as_str(), buf)?;
+    Ok(())
+}
+
+/// the config file of the command line being completed, the same default as the commands
+fn config_file() -> Option<PathBuf> {
+    // the shell calls "ssh-view -- ssh-view <args>"
+    let args = env::args().skip_while(|arg| arg != "--").skip(2).collect::<Vec<String>>();
+    #[allow(deprecated)]
+    let config = config_arg(&args)
+        .map(PathBuf::from)
+        .or(env::home_dir().map(|home| home.join(".ssh/config")))?;
+    config.canonicalize().ok()
+}
+
+/// the "-c" or "--config" of the args of the completed command, after the subcommand "-c" is its "--command"
+fn config_arg(args: &[String]) -> Option<String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--config" => return args.next().cloned(),
+            _ if arg.starts_with("--config=") => return arg.strip_prefix("--config=").map(String::from),
+            _ if arg.starts_with("-c") && !arg.starts_with("--") => return arg.strip_prefix("-c").map(String::from),
+            _ if arg.starts_with('-') => {}
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// the rows of the table without the titles in the order of "show --sort frecency", none when the config file can't be
+/// read
+fn rows() -> Vec<Vec<String>> {
+    config_file()
+        .and_then(|config_file| {
+            let data = read_ssh_config_file(&config_file).ok()?;
+            let data = add_pins(data, &Metadata::load(&config_file).unwrap_or_default());
+            // the hosts keep the order of the config file when the history can't be read
+            Some(sort_rows(data.clone(), &SortOrder::Frecency, &config_file).unwrap_or(data))
+        })
+        .map(|data| data.into_iter().skip(1).collect())
+        .unwrap_or_default()
+}
+
+/// the pin shortcuts, the connection names with the address they connect to and "tag:<Tag>" for every tag
+pub fn selection_candidates() -> Vec<CompletionCandidate> {
+    selection_candidates_of(&rows())
+}
+
+fn selection_candidates_of(rows: &[Vec<String>]) -> Vec<CompletionCandidate> {
+    // the pinned hosts are the ones used every day, so their shortcuts go first
+    let mut candidates = rows
+        .iter()
+        .filter(|row| !row[7].is_empty())
+        .map(|row| CompletionCandidate::new(row[7].as_str()).help(Some(row[1].clone().into())))
+        .collect::<Vec<CompletionCandidate>>();
+    // every name of the Host line selects it, the whole line would be split or quoted by the shell
+    candidates.extend(rows.iter().filter(|row| !crate::ssh_config::is_pattern(row[1].as_str())).flat_map(|row| {
+        let help = host_help(row);
+        row[1].split_whitespace().map(move |name| {
+            CompletionCandidate::new(name).help((!help.is_empty()).then(|| help.clone().into()))
+        })
+    }));
+    candidates.extend(tag_candidates_of(rows).into_iter().map(|tag| tag.add_prefix("tag:")));
+    candidates
+}
+
+/// the address of the host followed by its description and notes, the preview of the host in the completion menu
+fn host_help(row: &[String]) -> String {
+    let address = match (row[3].is_empty(), row[2].is_empty()) {
+        (_, true) => String::new(),
+        (true, false) => row[2].clone(),
+        (false, false) => format!("{}@{}", row[3], row[2]),
+    };
+    [address.as_str(), row[5].as_str(), row[6].lines().collect::<Vec<&str>>().join(" ").as_str()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" - ")
+}
+
+/// the tags of the hosts with the hosts that have them
+pub fn tag_candidates() -> Vec<CompletionCandidate> {
+    tag_candidates_of(&rows())
+}
+
+fn tag_candidates_of(rows: &[Vec<String>]) -> Vec<CompletionCandidate> {
+    let mut hosts: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for row in rows {
+        for tag in split_tags(row[4].as_str()) {
+            hosts.entry(tag).or_default().push(row[1].clone());
+        }
+    }
+    hosts
+        .into_iter()
+        .map(|(tag, hosts)| CompletionCandidate::new(tag).help(Some(hosts.join(", ").into())))
+        .collect()
+}
+
+/// the names of the saved tunnel profiles with the host they connect to
+pub fn profile_candidates() -> Vec<CompletionCandidate> {
+    config_file()
+        .and_then(|config_file| Metadata::load(&config_file).ok())
+        .map(|metadata| {
+            metadata
+                .tunnels
+                .iter()
+                .map(|(name, profile)| CompletionCandidate::new(name).help(Some(profile.host.clone().into())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(host_name: &str, user: &str, description: &str, notes: &str) -> Vec<String> {
+        ["0", "web", host_name, user, "", description, notes, ""].map(String::from).to_vec()
+    }
+
+    fn host(name: &str, tags: &str, pin: &str) -> Vec<String> {
+        ["0", name, "", "", tags, "", "", pin].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn selection_candidates_with_the_pins_first() {
+        let rows = [host("db", "prod", "@1"), host("web web.prod", "prod", ""), host("*.example.com", "", "")];
+        let values = selection_candidates_of(&rows)
+            .iter()
+            .map(|candidate| candidate.get_value().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(values, ["@1", "db", "web", "web.prod", "tag:prod"]);
+    }
+
+    #[test]
+    fn config_only_before_the_subcommand() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        assert_eq!(config_arg(&args(&["-c", "/tmp/cfg", "use", ""])), Some(String::from("/tmp/cfg")));
+        assert_eq!(config_arg(&args(&["--dry-run", "--config=/tmp/cfg", "use"])), Some(String::from("/tmp/cfg")));
+        assert_eq!(config_arg(&args(&["-c/tmp/cfg", "use"])), Some(String::from("/tmp/cfg")));
+        // the "-c" of the subcommands is the command to connect with
+        assert_eq!(config_arg(&args(&["use", "-c", "mosh", ""])), None);
+        assert_eq!(config_arg(&args(&["--print", "tunnel", "--command", "autossh"])), None);
+    }
+
+    #[test]
+    fn host_help_shows_the_description_and_notes() {
+        assert_eq!(host_help(&row("10.0.0.1", "admin", "", "")), "admin@10.0.0.1");
+        assert_eq!(
+            host_help(&row("10.0.0.1", "", "web server", "reboot on Sundays\nask ops first")),
+            "10.0.0.1 - web server - reboot on Sundays ask ops first"
+        );
+        assert_eq!(host_help(&row("", "", "", "no address")), "no address");
+        assert_eq!(host_help(&row("", "", "", "")), "");
+    }
+}
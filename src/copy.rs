@@ -0,0 +1,292 @@
+use crate::error::CommandError;
+use crate::launch::{command_line, LaunchMode};
+use clap::ValueEnum;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Program used to copy the files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CopyBackend {
+    Scp,
+    Rsync,
+    Sftp,
+}
+
+impl CopyBackend {
+    pub fn program(&self) -> &'static str {
+        match self {
+            CopyBackend::Scp => "scp",
+            CopyBackend::Rsync => "rsync",
+            CopyBackend::Sftp => "sftp",
+        }
+    }
+}
+
+/// Path of a file in the local machine or in one of the hosts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Local(String),
+    Remote { host: String, path: String },
+}
+
+impl Location {
+    /// "con:<path>" is a path in the selected connection and "<alias>:<path>" a path in any other connection of the table,
+    /// anything else is left as it is
+    pub fn parse(path: &str, connection_name: &str, aliases: &[String]) -> Location {
+        if let Some(path) = path.strip_prefix("con:") {
+            return Location::Remote {
+                host: connection_name.to_string(),
+                path: path.to_string(),
+            };
+        }
+        match path.split_once(':') {
+            Some((host, path)) if aliases.iter().any(|alias| alias == host) => Location::Remote {
+                host: host.to_string(),
+                path: path.to_string(),
+            },
+            _ => Location::Local(path.to_string()),
+        }
+    }
+
+    fn host(&self) -> Option<&str> {
+        match self {
+            Location::Local(_) => None,
+            Location::Remote { host, .. } => Some(host.as_str()),
+        }
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            Location::Local(path) => path.as_str(),
+            Location::Remote { path, .. } => path.as_str(),
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Local(path) => write!(f, "{}", path),
+            Location::Remote { host, path } => write!(f, "{}:{}", host, path),
+        }
+    }
+}
+
+/// returns the hosts of the remote locations, each one once
+pub fn remote_hosts<'a>(sources: &'a [Location], destination: &'a Location) -> Vec<&'a str> {
+    let mut hosts = vec![];
+    for host in sources.iter().chain([destination]).filter_map(Location::host) {
+        if !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+    hosts
+}
+
+/// Command that copies the files, sftp gets the transfers from a batch in its stdin
+pub struct CopyCommand {
+    pub command: Command,
+    pub batch: Option<String>,
+}
+
+/// Options of the copy that don't depend on the files
+pub struct CopyOptions<'a> {
+    pub via: CopyBackend,
+    /// program to run instead of the one of the backend
+    pub command: &'a Option<String>,
+    pub recursive: bool,
+    pub args: &'a Option<Vec<String>>,
+}
+
+pub fn copy_command(
+    sources: &[Location],
+    destination: &Location,
+    options: &CopyOptions,
+) -> Result<CopyCommand, Box<dyn Error>> {
+    let program = options.command.as_deref().unwrap_or(options.via.program());
+    let mut command = Command::new(program);
+    command
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    let remote_sources = sources.iter().filter(|source| source.host().is_some()).count();
+    let mut batch = None;
+    match options.via {
+        CopyBackend::Scp => {
+            if options.recursive {
+                command.arg("-r");
+            }
+            // copies between two hosts through the local machine, the hosts may not know each other's aliases
+            if remote_sources > 0 && destination.host().is_some() {
+                command.arg("-3");
+            }
+        }
+        CopyBackend::Rsync => {
+            if remote_sources > 0 && destination.host().is_some() {
+                return Err(Box::new(CommandError::new("Copy", "rsync can't copy between two remote hosts")));
+            }
+            let hosts = sources.iter().filter_map(|source| source.host()).collect::<Vec<&str>>();
+            if remote_sources > 0 && (remote_sources != sources.len() || hosts.iter().any(|host| *host != hosts[0])) {
+                return Err(Box::new(CommandError::new("Copy", "rsync needs every source in the same host")));
+            }
+            if options.recursive {
+                command.arg("-r");
+            }
+        }
+        CopyBackend::Sftp => {
+            let (host, transfer) = sftp_transfer(sources, destination)?;
+            command.arg("-b").arg("-");
+            if let Some(args) = options.args {
+                command.args(args.iter());
+            }
+            command.arg(host).stdin(Stdio::piped());
+            let flag = if options.recursive { " -r" } else { "" };
+            batch = Some(
+                sources
+                    .iter()
+                    .map(|source| {
+                        format!(
+                            "{transfer}{flag} {} {}\n",
+                            sftp_quote(source.path()),
+                            sftp_quote(destination.path())
+                        )
+                    })
+                    .collect::<String>(),
+            );
+            return Ok(CopyCommand { command, batch });
+        }
+    }
+    if let Some(args) = options.args {
+        command.args(args.iter());
+    }
+    command
+        .args(sources.iter().map(|source| source.to_string()))
+        .arg(destination.to_string());
+    Ok(CopyCommand { command, batch })
+}
+
+/// returns the host sftp connects to and whether the files are uploaded or downloaded
+fn sftp_transfer<'a>(
+    sources: &'a [Location],
+    destination: &'a Location,
+) -> Result<(&'a str, &'static str), Box<dyn Error>> {
+    let source_hosts = sources.iter().map(|source| source.host()).collect::<Vec<Option<&str>>>();
+    match destination.host() {
+        Some(host) if source_hosts.iter().all(|source| source.is_none()) => Ok((host, "put")),
+        None if source_hosts.iter().all(|source| source.is_some() && *source == source_hosts[0]) => {
+            Ok((source_hosts[0].unwrap_or_default(), "get"))
+        }
+        _ => Err(Box::new(CommandError::new(
+            "Copy",
+            "sftp can only copy local files to one host or files of one host to the local machine",
+        ))),
+    }
+}
+
+/// quotes the path for the sftp batch
+pub fn sftp_quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// runs the copy, or only shows it with its sftp batch
+pub fn run_copy(mut copy: CopyCommand, launch: &LaunchMode) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+    let Some(batch) = copy.batch else {
+        return launch.run(&mut copy.command);
+    };
+    match launch {
+        LaunchMode::Spawn => {
+            let mut child = copy.command.spawn()?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(batch.as_bytes())?;
+            }
+            return Ok(Some(child.wait()?));
+        }
+        LaunchMode::DryRun => eprint!("would run: {} <<EOF\n{}EOF\n", command_line(&copy.command), batch),
+        LaunchMode::Print => print!("{} <<EOF\n{}EOF\n", command_line(&copy.command), batch),
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::args;
+
+    fn remote(host: &str, path: &str) -> Location {
+        Location::Remote {
+            host: host.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    fn options(via: CopyBackend, recursive: bool) -> CopyOptions<'static> {
+        CopyOptions {
+            via,
+            command: &None,
+            recursive,
+            args: &None,
+        }
+    }
+
+    fn local(path: &str) -> Location {
+        Location::Local(path.to_string())
+    }
+
+    #[test]
+    fn parse_locations() {
+        let aliases = [String::from("web")];
+        assert_eq!(Location::parse("con:/etc/hosts", "db", &aliases), remote("db", "/etc/hosts"));
+        assert_eq!(Location::parse("web:logs", "db", &aliases), remote("web", "logs"));
+        assert_eq!(Location::parse("other:file", "db", &aliases), local("other:file"));
+    }
+
+    #[test]
+    fn remote_hosts_of_the_locations() {
+        let sources = [remote("web", "/a"), local("b"), remote("db", "/c"), remote("web", "/d")];
+        assert_eq!(remote_hosts(&sources, &remote("db", "/tmp")), ["web", "db"]);
+        assert!(remote_hosts(&[local("a")], &local("b")).is_empty());
+    }
+
+    #[test]
+    fn scp_between_two_hosts() {
+        let copy = copy_command(&[remote("web", "/a")], &remote("db", "/b"), &options(CopyBackend::Scp, true)).unwrap();
+        assert_eq!(copy.command.get_program(), "scp");
+        assert_eq!(args(&copy.command), ["-r", "-3", "web:/a", "db:/b"]);
+        assert!(copy.batch.is_none());
+    }
+
+    #[test]
+    fn rsync_with_args_and_command() {
+        let command = Some(String::from("/usr/local/bin/rsync"));
+        let extra = Some(vec![String::from("-az")]);
+        let options = CopyOptions {
+            via: CopyBackend::Rsync,
+            command: &command,
+            recursive: false,
+            args: &extra,
+        };
+        let copy = copy_command(&[local("dir/")], &remote("web", "/srv"), &options).unwrap();
+        assert_eq!(copy.command.get_program(), "/usr/local/bin/rsync");
+        assert_eq!(args(&copy.command), ["-az", "dir/", "web:/srv"]);
+    }
+
+    #[test]
+    fn rsync_rejects_two_remote_hosts() {
+        assert!(copy_command(&[remote("web", "/a")], &remote("db", "/b"), &options(CopyBackend::Rsync, false)).is_err());
+        let sources = [remote("web", "/a"), remote("db", "/b")];
+        assert!(copy_command(&sources, &local("."), &options(CopyBackend::Rsync, false)).is_err());
+    }
+
+    #[test]
+    fn sftp_writes_a_batch() {
+        let sources = [remote("web", "/var/log/a b.log")];
+        let copy = copy_command(&sources, &local("."), &options(CopyBackend::Sftp, true)).unwrap();
+        assert_eq!(args(&copy.command), ["-b", "-", "web"]);
+        assert_eq!(copy.batch.as_deref(), Some("get -r \"/var/log/a b.log\" \".\"\n"));
+        let upload = copy_command(&[local("f")], &remote("web", "/tmp"), &options(CopyBackend::Sftp, false)).unwrap();
+        assert_eq!(upload.batch.as_deref(), Some("put \"f\" \"/tmp\"\n"));
+        assert!(copy_command(&sources, &remote("db", "/tmp"), &options(CopyBackend::Sftp, false)).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// error of a subcommand of ssh-view, the message starts with the name of the subcommand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandError {
+    command: &'static str,
+    message: String,
+}
+
+impl CommandError {
+    pub fn new(command: &'static str, message: &str) -> CommandError {
+        CommandError {
+            command,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Error for CommandError {}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} Error: {}", self.command, self.message)
+    }
+}
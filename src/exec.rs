@@ -0,0 +1,283 @@
+use crate::OutputFormat;
+use crate::error::CommandError;
+use prettytable::{color, Attr, Cell, Row, Table};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+/// Result of running the remote command on a single host
+#[derive(Serialize)]
+pub struct ExecResult {
+    pub host: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
+
+impl ExecResult {
+    fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// runs the remote command on every host using at most `jobs` concurrent ssh processes
+pub fn exec_on_hosts(
+    hosts: Vec<String>,
+    command: &str,
+    args: &Option<Vec<String>>,
+    remote_command: &[String],
+    jobs: usize,
+    group: bool,
+    format: &OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    if hosts.is_empty() {
+        return Err(Box::new(CommandError::new("Exec", "no connection matches the filter")));
+    }
+    let capture = group || *format == OutputFormat::Json;
+    let width = hosts.iter().map(|host| host.len()).max().unwrap_or(0);
+    let results = run_parallel(hosts, jobs, |host| {
+        let result = exec_on_host(&host, command, args, remote_command, capture, width);
+        if group && *format != OutputFormat::Json {
+            print_group(&result);
+        }
+        result
+    });
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+        OutputFormat::Table => print_summary(&results),
+    }
+    let failed = results.iter().filter(|result| !result.success()).count();
+    if failed > 0 {
+        return Err(Box::new(CommandError::new(
+            "Exec",
+            format!("{} of {} hosts failed", failed, results.len()).as_str(),
+        )));
+    }
+    Ok(())
+}
+
+/// applies the function to every item using at most `jobs` threads, the results keep the order of the items
+pub fn run_parallel<T, R, F>(items: Vec<T>, jobs: usize, function: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Sync,
+{
+    let total = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate().collect::<VecDeque<(usize, T)>>());
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..total).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, total.max(1)) {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap().pop_front();
+                let Some((index, item)) = next else {
+                    break;
+                };
+                let result = function(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// builds the command that runs the remote command on the host
+pub fn exec_command(host: &str, command: &str, args: &Option<Vec<String>>, remote_command: &[String]) -> Command {
+    let mut command = Command::new(command);
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command.arg(host).args(remote_command);
+    command
+}
+
+fn exec_on_host(
+    host: &str,
+    command: &str,
+    args: &Option<Vec<String>>,
+    remote_command: &[String],
+    capture: bool,
+    width: usize,
+) -> ExecResult {
+    let start = Instant::now();
+    let child = exec_command(host, command, args, remote_command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            return ExecResult {
+                host: host.to_string(),
+                exit_code: None,
+                duration_ms: start.elapsed().as_millis(),
+                stdout: None,
+                stderr: Some(e.to_string()),
+            };
+        }
+    };
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let (stdout, stderr) = if capture {
+        thread::scope(|scope| {
+            let stdout = scope.spawn(|| read_all(stdout));
+            let stderr = scope.spawn(|| read_all(stderr));
+            (Some(stdout.join().unwrap()), Some(stderr.join().unwrap()))
+        })
+    } else {
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                    println!("[{host:width$}] {line}");
+                }
+            });
+            scope.spawn(|| {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    eprintln!("[{host:width$}] {line}");
+                }
+            });
+        });
+        (None, None)
+    };
+    let exit_code = child.wait().ok().and_then(|status| status.code());
+    ExecResult {
+        host: host.to_string(),
+        exit_code,
+        duration_ms: start.elapsed().as_millis(),
+        stdout,
+        stderr,
+    }
+}
+
+fn read_all(mut reader: impl Read) -> String {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).expect("Error reading command output");
+    String::from_utf8_lossy(&data).to_string()
+}
+
+fn print_group(result: &ExecResult) {
+    let stdout = std::io::stdout();
+    let _lock = stdout.lock();
+    println!("=== {} ({}) ===", result.host, exit_code_text(result));
+    if let Some(output) = &result.stdout {
+        print!("{output}");
+    }
+    if let Some(output) = &result.stderr {
+        eprint!("{output}");
+    }
+}
+
+fn exit_code_text(result: &ExecResult) -> String {
+    match result.exit_code {
+        Some(code) => code.to_string(),
+        None => match &result.stderr {
+            Some(error) if result.stdout.is_none() => format!("error: {}", error.trim()),
+            _ => String::from("killed"),
+        },
+    }
+}
+
+fn print_summary(results: &[ExecResult]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Host", "Exit Code", "Duration"]
+            .iter()
+            .map(|title| {
+                Cell::new(title)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::GREEN))
+            })
+            .collect::<Vec<Cell>>(),
+    ));
+    for result in results {
+        let cell_color = if result.success() { color::CYAN } else { color::RED };
+        table.add_row(Row::new(
+            [
+                result.host.clone(),
+                exit_code_text(result),
+                format!("{:.2}s", result.duration_ms as f64 / 1000f64),
+            ]
+                .iter()
+                .map(|cell| Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(cell_color)))
+                .collect::<Vec<Cell>>(),
+        ));
+    }
+    table.printstd();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{strings, TempFile};
+    use std::time::Duration;
+
+    /// writes a script run by sh as the ssh command, it prints the host and the remote command and fails for "down"
+    fn stub_script(name: &str) -> TempFile {
+        let script = "host=$1\nshift\n[ \"$host\" = slow ] && sleep 0.3\n\
+                      [ \"$host\" = down ] && echo \"connection refused\" >&2 && exit 255\necho \"$host: $*\"\n";
+        TempFile::new(format!("exec-{name}").as_str(), script)
+    }
+
+    #[test]
+    fn run_parallel_keeps_the_order_of_the_items() {
+        let results = run_parallel(vec![30, 0, 20, 10], 4, |millis: u64| {
+            thread::sleep(Duration::from_millis(millis));
+            millis
+        });
+        assert_eq!(results, [30, 0, 20, 10]);
+        assert_eq!(run_parallel(vec![1, 2, 3], 0, |item: i32| item * 2), [2, 4, 6]);
+        assert!(run_parallel(vec![], 4, |item: i32| item).is_empty());
+    }
+
+    #[test]
+    fn exec_captures_the_output_and_exit_code_of_each_host() {
+        let stub = stub_script("capture");
+        let args = Some(vec![stub.path().to_string_lossy().to_string()]);
+        let remote_command = strings(&["uptime", "-p"]);
+        let results = run_parallel(strings(&["slow", "web", "down"]), 3, |host| {
+            exec_on_host(&host, "sh", &args, &remote_command, true, 4)
+        });
+        assert_eq!(results.iter().map(|result| result.host.as_str()).collect::<Vec<&str>>(), ["slow", "web", "down"]);
+        assert_eq!(results[1].stdout.as_deref(), Some("web: uptime -p\n"));
+        assert!(results[1].success());
+        assert_eq!(results[2].exit_code, Some(255));
+        assert_eq!(results[2].stderr.as_deref(), Some("connection refused\n"));
+        assert_eq!(exit_code_text(&results[2]), "255");
+        let json = serde_json::to_value(&results[1]).unwrap();
+        assert_eq!(json["host"], "web");
+        assert_eq!(json["exit_code"], 0);
+        assert_eq!(json["stdout"], "web: uptime -p\n");
+    }
+
+    #[test]
+    fn exec_summary_counts_the_failed_hosts() {
+        let stub = stub_script("summary");
+        let args = Some(vec![stub.path().to_string_lossy().to_string()]);
+        let remote_command = strings(&["true"]);
+        let exec = |hosts: &[&str], group: bool, format: OutputFormat| {
+            exec_on_hosts(strings(hosts), "sh", &args, &remote_command, 2, group, &format)
+        };
+        let failed = exec(&["web", "down", "db"], false, OutputFormat::Json);
+        let succeeded = exec(&["web", "db"], true, OutputFormat::Table);
+        assert_eq!(failed.unwrap_err().to_string(), "Exec Error: 1 of 3 hosts failed");
+        assert!(succeeded.is_ok());
+        assert!(exec(&[], false, OutputFormat::Table).is_err());
+    }
+
+    #[test]
+    fn exec_reports_a_command_that_can_not_start() {
+        let result = exec_on_host("web", "/nonexistent/ssh", &None, &[], false, 3);
+        assert_eq!(result.exit_code, None);
+        assert!(exit_code_text(&result).starts_with("error: "));
+        assert!(serde_json::to_value(&result).unwrap().get("stdout").is_none());
+    }
+}
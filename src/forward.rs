@@ -0,0 +1,529 @@
+use crate::error::CommandError;
+use crate::tunnel::TunnelMode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::path::Path;
+use std::str::FromStr;
+
+const AUTO_PORT: &str = "auto";
+
+/// Local port of a tunnel, "auto" picks a free port when the tunnel starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalPort {
+    Port(u16),
+    Auto,
+}
+
+impl FromStr for LocalPort {
+    type Err = String;
+
+    fn from_str(port: &str) -> Result<Self, Self::Err> {
+        if port == AUTO_PORT {
+            return Ok(LocalPort::Auto);
+        }
+        port.parse::<u16>()
+            .map(LocalPort::Port)
+            .map_err(|_| format!("invalid port \"{port}\", use a number or \"{AUTO_PORT}\""))
+    }
+}
+
+impl Display for LocalPort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocalPort::Port(port) => write!(f, "{port}"),
+            LocalPort::Auto => write!(f, "{AUTO_PORT}"),
+        }
+    }
+}
+
+impl Serialize for LocalPort {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LocalPort::Port(port) => serializer.serialize_u16(*port),
+            LocalPort::Auto => serializer.serialize_str(AUTO_PORT),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LocalPort {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawPort {
+            Number(u16),
+            Text(String),
+        }
+        match RawPort::deserialize(deserializer)? {
+            RawPort::Number(port) => Ok(LocalPort::Port(port)),
+            RawPort::Text(port) => port.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Range of local ports to pick from when the port is "auto"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid port range \"{range}\", use \"<start>-<end>\"");
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let start = start.trim().parse::<u16>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<u16>().map_err(|_| invalid())?;
+        if start == 0 || start > end {
+            return Err(invalid());
+        }
+        Ok(PortRange { start, end })
+    }
+}
+
+impl Display for PortRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardKind {
+    Local,
+    Remote,
+    Dynamic,
+}
+
+impl ForwardKind {
+    pub fn option(&self) -> &'static str {
+        match self {
+            ForwardKind::Local => "-L",
+            ForwardKind::Remote => "-R",
+            ForwardKind::Dynamic => "-D",
+        }
+    }
+}
+
+/// Forward of a tunnel using the same specification as the -L, -R and -D options of ssh
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Forward {
+    pub kind: ForwardKind,
+    pub spec: String,
+}
+
+/// Side of the forward that accepts the connections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    Port {
+        bind: Option<String>,
+        port: LocalPort,
+    },
+    Socket(String),
+}
+
+impl Forward {
+    pub fn parse(kind: ForwardKind, spec: &str) -> Result<Forward, String> {
+        let forward = Forward {
+            kind,
+            spec: spec.to_string(),
+        };
+        forward.listen()?;
+        Ok(forward)
+    }
+
+    pub fn parse_local(spec: &str) -> Result<Forward, String> {
+        Forward::parse(ForwardKind::Local, spec)
+    }
+
+    pub fn parse_remote(spec: &str) -> Result<Forward, String> {
+        Forward::parse(ForwardKind::Remote, spec)
+    }
+
+    pub fn parse_dynamic(spec: &str) -> Result<Forward, String> {
+        Forward::parse(ForwardKind::Dynamic, spec)
+    }
+
+    /// returns the listening side of the forward, checking the specification is valid
+    pub fn listen(&self) -> Result<Listen, String> {
+        let parts = split_spec(self.spec.as_str());
+        let invalid = || format!("invalid {} forward \"{}\"", self.kind.option(), self.spec);
+        if parts[0].contains('/') {
+            let listen = Listen::Socket(parts[0].clone());
+            return match self.valid_target(&listen, &parts[1..]) {
+                true => Ok(listen),
+                false => Err(invalid()),
+            };
+        }
+        // the bind address is optional, so "port:host:hostport" and "bind:port:socket" have the same length
+        if parts.len() >= 2 {
+            if let Some(port) = self.listen_port(parts[1].as_str()) {
+                let listen = Listen::Port {
+                    bind: Some(parts[0].clone()),
+                    port,
+                };
+                if self.valid_target(&listen, &parts[2..]) {
+                    return Ok(listen);
+                }
+            }
+        }
+        let listen = Listen::Port {
+            bind: None,
+            port: self.listen_port(parts[0].as_str()).ok_or_else(invalid)?,
+        };
+        match self.valid_target(&listen, &parts[1..]) {
+            true => Ok(listen),
+            false => Err(invalid()),
+        }
+    }
+
+    /// parses the listening port, only the ports on this machine can be picked automatically
+    fn listen_port(&self, port: &str) -> Option<LocalPort> {
+        match port.parse::<LocalPort>() {
+            Ok(LocalPort::Auto) if !self.listens_locally() => None,
+            port => port.ok(),
+        }
+    }
+
+    fn valid_target(&self, listen: &Listen, target: &[String]) -> bool {
+        match (self.kind, target.len()) {
+            (ForwardKind::Dynamic, 0) => !matches!(listen, Listen::Socket(_)),
+            (ForwardKind::Dynamic, _) => false,
+            // a remote forward without destination makes ssh act as a SOCKS proxy on the remote side
+            (ForwardKind::Remote, 0) => !matches!(listen, Listen::Socket(_)),
+            (_, 1) => target[0].contains('/'),
+            (_, 2) => !target[0].is_empty() && target[1].parse::<u16>().is_ok(),
+            _ => false,
+        }
+    }
+
+    /// checks if the forward listens on this machine
+    pub fn listens_locally(&self) -> bool {
+        self.kind != ForwardKind::Remote
+    }
+}
+
+impl Display for Forward {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind.option(), self.spec)
+    }
+}
+
+impl From<&TunnelMode> for Forward {
+    fn from(mode: &TunnelMode) -> Self {
+        let kind = match mode {
+            TunnelMode::Local { .. } => ForwardKind::Local,
+            TunnelMode::Remote { .. } => ForwardKind::Remote,
+            TunnelMode::Dynamic { .. } => ForwardKind::Dynamic,
+        };
+        Forward {
+            kind,
+            spec: mode.forward_args().1,
+        }
+    }
+}
+
+/// splits a forward specification by ':' keeping the IPv6 addresses between brackets together
+fn split_spec(spec: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut in_brackets = false;
+    for c in spec.chars() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            ':' if !in_brackets => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+fn is_wildcard(bind: &Option<String>) -> bool {
+    matches!(bind.as_deref(), Some("") | Some("*") | Some("0.0.0.0") | Some("::"))
+}
+
+fn is_loopback(bind: &Option<String>) -> bool {
+    matches!(bind.as_deref(), None | Some("localhost") | Some("127.0.0.1") | Some("::1"))
+}
+
+fn listens_collide(first: &Listen, second: &Listen) -> bool {
+    match (first, second) {
+        (Listen::Socket(first), Listen::Socket(second)) => first == second,
+        (
+            Listen::Port { bind: first_bind, port: first_port },
+            Listen::Port { bind: second_bind, port: second_port },
+        ) => {
+            // port 0 asks ssh to allocate a free port
+            matches!(first_port, LocalPort::Port(port) if *port != 0)
+                && first_port == second_port
+                && (first_bind == second_bind
+                || is_wildcard(first_bind)
+                || is_wildcard(second_bind)
+                || is_loopback(first_bind) && is_loopback(second_bind))
+        }
+        _ => false,
+    }
+}
+
+/// checks that no two forwards listen on the same port or socket of the same side of the tunnel
+pub fn validate_forwards(forwards: &[Forward]) -> Result<(), Box<dyn Error>> {
+    if forwards.is_empty() {
+        return Err(Box::new(CommandError::new("Tunnel Mode", "no tunnel mode selected")));
+    }
+    let listens = forwards
+        .iter()
+        .map(|forward| forward.listen().map(|listen| (forward, listen)))
+        .collect::<Result<Vec<(&Forward, Listen)>, String>>()
+        .map_err(|e| Box::new(CommandError::new("Tunnel Mode", e.as_str())))?;
+    for (index, (first, first_listen)) in listens.iter().enumerate() {
+        for (second, second_listen) in listens.iter().skip(index + 1) {
+            if first.listens_locally() == second.listens_locally() && listens_collide(first_listen, second_listen) {
+                return Err(Box::new(CommandError::new(
+                    "Tunnel Mode",
+                    format!("the forwards \"{first}\" and \"{second}\" listen on the same port").as_str(),
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// checks the local ports of the forwards are free, picking a free port from the range for the "auto" ones
+pub fn allocate_local_ports(forwards: &[Forward], range: &PortRange) -> Result<Vec<Forward>, Box<dyn Error>> {
+    let mut used_ports = vec![];
+    let mut allocated = vec![];
+    for forward in forwards.iter() {
+        if !forward.listens_locally() {
+            allocated.push(forward.clone());
+            continue;
+        }
+        match forward.listen().map_err(|e| Box::new(CommandError::new("Tunnel Mode", e.as_str())))? {
+            Listen::Socket(path) => {
+                if Path::new(path.as_str()).exists() {
+                    return Err(Box::new(CommandError::new(
+                        "Tunnel Mode",
+                        format!("the local socket {path} of \"{forward}\" already exists").as_str(),
+                    )));
+                }
+                allocated.push(forward.clone());
+            }
+            Listen::Port { bind, port: LocalPort::Port(port) } => {
+                check_local_port(&bind, port).map_err(|e| {
+                    Box::new(CommandError::new(
+                        "Tunnel Mode",
+                        format!("the local port {port} of \"{forward}\" is not available: {e}").as_str(),
+                    ))
+                })?;
+                used_ports.push(port);
+                allocated.push(forward.clone());
+            }
+            Listen::Port { bind, port: LocalPort::Auto } => {
+                let port = (range.start..=range.end)
+                    .filter(|port| !used_ports.contains(port))
+                    .find(|port| check_local_port(&bind, *port).is_ok())
+                    .ok_or_else(|| {
+                        Box::new(CommandError::new(
+                            "Tunnel Mode",
+                            format!("no free local port in the range {range} for \"{forward}\"").as_str(),
+                        ))
+                    })?;
+                used_ports.push(port);
+                let index = usize::from(bind.is_some());
+                allocated.push(Forward {
+                    kind: forward.kind,
+                    spec: replace_segment(forward.spec.as_str(), index, port.to_string().as_str()),
+                });
+            }
+        }
+    }
+    Ok(allocated)
+}
+
+/// replaces the port of the only forward that listens on a local port, used by "--local-port" to change the port
+/// of the tunnel mode or of a saved tunnel
+pub fn set_local_port(forwards: &mut [Forward], port: LocalPort) -> Result<(), Box<dyn Error>> {
+    let mut local = forwards.iter_mut().filter_map(|forward| match forward.listen() {
+        Ok(Listen::Port { bind, .. }) if forward.listens_locally() => Some((forward, bind)),
+        _ => None,
+    });
+    let (Some((forward, bind)), None) = (local.next(), local.next()) else {
+        return Err(Box::new(CommandError::new(
+            "Tunnel Mode",
+            "--local-port needs a tunnel with a single forward listening on a local port",
+        )));
+    };
+    forward.spec = replace_segment(forward.spec.as_str(), usize::from(bind.is_some()), port.to_string().as_str());
+    Ok(())
+}
+
+/// tries to listen on the local port the same way ssh would do
+fn check_local_port(bind: &Option<String>, port: u16) -> Result<(), Box<dyn Error>> {
+    let address = match bind.as_deref() {
+        None | Some("localhost") => "127.0.0.1",
+        Some("") | Some("*") => "0.0.0.0",
+        Some(address) => address,
+    };
+    let addresses = (address, port).to_socket_addrs()?.collect::<Vec<_>>();
+    TcpListener::bind(addresses.as_slice())?;
+    Ok(())
+}
+
+/// replaces the segment of the forward specification, keeping the IPv6 addresses between brackets together
+fn replace_segment(spec: &str, index: usize, value: &str) -> String {
+    let mut segments = vec![String::new()];
+    let mut in_brackets = false;
+    for c in spec.chars() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            ':' if !in_brackets => {
+                segments.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        segments.last_mut().unwrap().push(c);
+    }
+    segments[index] = value.to_string();
+    segments.join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(bind: Option<&str>, port: LocalPort) -> Listen {
+        Listen::Port {
+            bind: bind.map(String::from),
+            port,
+        }
+    }
+
+    #[test]
+    fn listen_local_forwards() {
+        let listen = |spec: &str| Forward::parse_local(spec).map(|forward| forward.listen().unwrap());
+        assert_eq!(listen("8080:localhost:80"), Ok(port(None, LocalPort::Port(8080))));
+        assert_eq!(listen("auto:localhost:80"), Ok(port(None, LocalPort::Auto)));
+        assert_eq!(listen("127.0.0.1:8080:db:5432"), Ok(port(Some("127.0.0.1"), LocalPort::Port(8080))));
+        assert_eq!(listen("[::1]:8080:[2001:db8::1]:80"), Ok(port(Some("::1"), LocalPort::Port(8080))));
+        assert_eq!(listen("8080:/run/app.sock"), Ok(port(None, LocalPort::Port(8080))));
+        assert_eq!(listen("/tmp/local.sock:db:5432"), Ok(Listen::Socket(String::from("/tmp/local.sock"))));
+    }
+
+    #[test]
+    fn listen_rejects_invalid_forwards() {
+        assert!(Forward::parse_local("8080").is_err());
+        assert!(Forward::parse_local("8080:localhost").is_err());
+        assert!(Forward::parse_local("8080:localhost:http").is_err());
+        assert!(Forward::parse_local("port:localhost:80").is_err());
+        assert!(Forward::parse_dynamic("1080:localhost:80").is_err());
+    }
+
+    #[test]
+    fn listen_remote_and_dynamic_forwards() {
+        let remote = Forward::parse_remote("9000").unwrap();
+        assert_eq!(remote.listen(), Ok(port(None, LocalPort::Port(9000))));
+        assert!(!remote.listens_locally());
+        // only the ports of this machine can be picked automatically
+        assert!(Forward::parse_remote("auto:localhost:80").is_err());
+        assert_eq!(Forward::parse_dynamic("auto").unwrap().listen(), Ok(port(None, LocalPort::Auto)));
+    }
+
+    fn local(spec: &str) -> Forward {
+        Forward::parse_local(spec).unwrap()
+    }
+
+    #[test]
+    fn set_the_local_port_of_the_single_local_forward() {
+        let mut forwards = vec![local("127.0.0.1:5432:db:5432"), Forward::parse_remote("9000").unwrap()];
+        set_local_port(&mut forwards, LocalPort::Auto).unwrap();
+        assert_eq!(forwards[0].spec, "127.0.0.1:auto:db:5432");
+        assert_eq!(forwards[1].spec, "9000");
+        let mut forwards = vec![local("5432:db:5432"), Forward::parse_dynamic("1080").unwrap()];
+        assert!(set_local_port(&mut forwards, LocalPort::Port(15432)).is_err());
+    }
+
+    #[test]
+    fn busy_local_ports_are_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let busy = listener.local_addr().unwrap().port();
+        assert!(check_local_port(&None, busy).is_err());
+        let forwards = vec![local(format!("{busy}:localhost:80").as_str())];
+        let range = PortRange { start: 20000, end: 29999 };
+        let error = allocate_local_ports(&forwards, &range).unwrap_err().to_string();
+        assert!(error.contains(format!("the local port {busy} of").as_str()), "{error}");
+    }
+
+    #[test]
+    fn auto_ports_skip_the_busy_ones() {
+        // a range of two ports whose first one is taken
+        let (listener, busy) = (1024..u16::MAX - 1)
+            .find_map(|port| {
+                let listener = TcpListener::bind(("127.0.0.1", port)).ok()?;
+                check_local_port(&None, port + 1).ok().map(|_| (listener, port))
+            })
+            .unwrap();
+        let range = PortRange { start: busy, end: busy + 1 };
+        let forwards = vec![local("auto:localhost:80"), Forward::parse_dynamic("auto").unwrap()];
+        let error = allocate_local_ports(&forwards, &range).unwrap_err().to_string();
+        assert!(error.contains("no free local port in the range"), "{error}");
+        let allocated = allocate_local_ports(&forwards[..1], &range).unwrap();
+        assert_eq!(allocated[0].spec, format!("{}:localhost:80", busy + 1));
+        drop(listener);
+    }
+
+    #[test]
+    fn auto_ports_keep_the_bind_address() {
+        let range = PortRange { start: 20000, end: 29999 };
+        let allocated = allocate_local_ports(&[local("127.0.0.1:auto:db:5432")], &range).unwrap();
+        let Listen::Port { bind, port: LocalPort::Port(port) } = allocated[0].listen().unwrap() else {
+            panic!("the port was not allocated");
+        };
+        assert_eq!(bind.as_deref(), Some("127.0.0.1"));
+        assert!((20000..=29999).contains(&port));
+    }
+
+    fn collide(first: &str, second: &str) -> bool {
+        validate_forwards(&[local(first), local(second)]).is_err()
+    }
+
+    #[test]
+    fn forwards_on_the_same_port_collide() {
+        assert!(collide("8080:localhost:80", "8080:localhost:81"));
+        // the wildcard address takes the port on every address
+        assert!(collide("0.0.0.0:8080:localhost:80", "10.0.0.5:8080:localhost:81"));
+        assert!(collide("10.0.0.5:8080:localhost:80", "*:8080:localhost:81"));
+        assert!(collide("[::]:8080:localhost:80", "127.0.0.1:8080:localhost:81"));
+        // ssh binds the forwards without address to the loopback one
+        assert!(collide("8080:localhost:80", "localhost:8080:localhost:81"));
+        assert!(collide("8080:localhost:80", "[::1]:8080:localhost:81"));
+    }
+
+    #[test]
+    fn forwards_on_different_addresses_or_sides_dont_collide() {
+        assert!(!collide("10.0.0.5:8080:localhost:80", "10.0.0.6:8080:localhost:81"));
+        assert!(!collide("10.0.0.5:8080:localhost:80", "127.0.0.1:8080:localhost:81"));
+        assert!(!collide("8080:localhost:80", "8081:localhost:80"));
+        // port 0 lets ssh pick a different port for each forward
+        assert!(!collide("0:localhost:80", "0:localhost:81"));
+        let remote = Forward::parse_remote("8080:localhost:80").unwrap();
+        assert!(validate_forwards(&[local("8080:localhost:80"), remote]).is_ok());
+    }
+
+    #[test]
+    fn socket_forwards_collide_on_the_same_path() {
+        assert!(collide("/tmp/app.sock:/run/app.sock", "/tmp/app.sock:db:5432"));
+        assert!(!collide("/tmp/app.sock:/run/app.sock", "/tmp/db.sock:db:5432"));
+        assert!(!collide("/tmp/app.sock:/run/app.sock", "8080:/run/app.sock"));
+    }
+
+    #[test]
+    fn validate_forwards_needs_a_forward() {
+        assert!(validate_forwards(&[]).is_err());
+    }
+}
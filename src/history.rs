@@ -0,0 +1,197 @@
+use crate::launch::LaunchMode;
+use crate::state::{format_elapsed, format_timestamp, load_state, now, save_state};
+use prettytable::{color, Attr, Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const HISTORY_STATE: &str = "history.json";
+/// oldest entries are dropped once the history has this many
+const HISTORY_SIZE: usize = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Activity {
+    Use,
+    Copy,
+    Tunnel,
+}
+
+impl Activity {
+    fn name(&self) -> &'static str {
+        match self {
+            Activity::Use => "use",
+            Activity::Copy => "copy",
+            Activity::Tunnel => "tunnel",
+        }
+    }
+}
+
+/// Connection made to a host, the hosts are the names in the config file they were made with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub config: PathBuf,
+    pub host: String,
+    pub activity: Activity,
+    pub started: u64,
+    /// seconds the command was running
+    pub duration: u64,
+    /// exit code of the command, tunnels don't have one as they may keep running in background
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+/// Last connection made to a host and how many were made
+pub struct RecentHost {
+    pub last: HistoryEntry,
+    pub uses: usize,
+}
+
+/// adds the connection to the history, the command doesn't fail when the history can't be saved
+pub fn record(
+    config_file: &Path,
+    host: &str,
+    activity: Activity,
+    started: Instant,
+    exit_code: Option<i32>,
+    launch: &LaunchMode,
+) {
+    if !launch.spawns() {
+        return;
+    }
+    let duration = started.elapsed().as_secs();
+    let entry = HistoryEntry {
+        config: config_file.to_path_buf(),
+        host: host.to_string(),
+        activity,
+        started: now().saturating_sub(duration),
+        duration,
+        exit_code,
+    };
+    let result = load_state::<Vec<HistoryEntry>>(HISTORY_STATE).and_then(|mut history| {
+        history.push(entry);
+        let excess = history.len().saturating_sub(HISTORY_SIZE);
+        history.drain(..excess);
+        save_state(HISTORY_STATE, &history)
+    });
+    if let Err(e) = result {
+        eprintln!("couldn't save the connection in the history: {e}");
+    }
+}
+
+/// returns the connections made with the config file, the oldest first
+pub fn history(config_file: &Path) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+    let history: Vec<HistoryEntry> = load_state(HISTORY_STATE)?;
+    Ok(history.into_iter().filter(|entry| entry.config == config_file).collect())
+}
+
+/// returns the hosts of the history with the most recently used first
+pub fn recent_hosts(config_file: &Path) -> Result<Vec<RecentHost>, Box<dyn Error>> {
+    Ok(recent_hosts_of(history(config_file)?))
+}
+
+fn recent_hosts_of(history: Vec<HistoryEntry>) -> Vec<RecentHost> {
+    let mut hosts: Vec<RecentHost> = vec![];
+    for entry in history.into_iter().rev() {
+        match hosts.iter_mut().find(|host| host.last.host == entry.host) {
+            Some(host) => host.uses += 1,
+            None => hosts.push(RecentHost { last: entry, uses: 1 }),
+        }
+    }
+    hosts
+}
+
+/// scores the hosts by how often and how recently they were used, each connection counts less as it gets older
+pub fn frecency(config_file: &Path) -> Result<HashMap<String, u64>, Box<dyn Error>> {
+    Ok(frecency_of(history(config_file)?, now()))
+}
+
+fn frecency_of(history: Vec<HistoryEntry>, now: u64) -> HashMap<String, u64> {
+    let mut scores: HashMap<String, u64> = HashMap::new();
+    for entry in history {
+        let days = now.saturating_sub(entry.started) / 86400;
+        let score = match days {
+            0..=3 => 100,
+            4..=14 => 70,
+            15..=31 => 50,
+            32..=90 => 30,
+            _ => 10,
+        };
+        *scores.entry(entry.host).or_default() += score;
+    }
+    scores
+}
+
+pub fn print_recent(hosts: &[RecentHost]) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Number", "Host", "Last Command", "Last Used", "Duration", "Exit Code", "Uses"]
+            .iter()
+            .map(|title| {
+                Cell::new(title)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::GREEN))
+            })
+            .collect::<Vec<Cell>>(),
+    ));
+    for (number, host) in hosts.iter().enumerate() {
+        let failed = host.last.exit_code.is_some_and(|code| code != 0);
+        table.add_row(Row::new(
+            [
+                number.to_string(),
+                host.last.host.clone(),
+                host.last.activity.name().to_string(),
+                format_timestamp(host.last.started),
+                format_elapsed(host.last.duration),
+                host.last.exit_code.map(|code| code.to_string()).unwrap_or_default(),
+                host.uses.to_string(),
+            ]
+                .iter()
+                .map(|cell| {
+                    Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(if failed { color::RED } else { color::CYAN }))
+                })
+                .collect::<Vec<Cell>>(),
+        ));
+    }
+    table.printstd();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86400;
+    const NOW: u64 = 1000 * DAY;
+
+    fn entry(host: &str, days_ago: u64) -> HistoryEntry {
+        HistoryEntry {
+            config: PathBuf::from("/home/me/.ssh/config"),
+            host: host.to_string(),
+            activity: Activity::Use,
+            started: NOW - days_ago * DAY,
+            duration: 60,
+            exit_code: Some(0),
+        }
+    }
+
+    #[test]
+    fn frecency_adds_less_for_older_connections() {
+        let scores = frecency_of(vec![entry("web", 100), entry("web", 20), entry("db", 2), entry("web", 0)], NOW);
+        assert_eq!(scores.get("web"), Some(&160));
+        assert_eq!(scores.get("db"), Some(&100));
+        let scores = frecency_of(vec![entry("old", 60), entry("week", 10)], NOW);
+        assert!(scores["week"] > scores["old"]);
+    }
+
+    #[test]
+    fn recent_hosts_with_the_last_used_first() {
+        let hosts = recent_hosts_of(vec![entry("web", 3), entry("db", 2), entry("web", 1)]);
+        assert_eq!(
+            hosts.iter().map(|host| (host.last.host.as_str(), host.uses)).collect::<Vec<(&str, usize)>>(),
+            [("web", 2), ("db", 1)]
+        );
+        assert_eq!(hosts[0].last.started, NOW - DAY);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::import::ImportedHost;
+use crate::ssh_config::SshHost;
+use clap::Subcommand;
+use serde_json::{Map, Value};
+use std::error::Error;
+
+/// column with the Host line of each host, the rest of the columns are the options and the structured comments
+const HOST: &str = "Host";
+/// prefix of the columns with the structured comments of ssh-view, "@tags" is "#@tags"
+const ANNOTATION_PREFIX: char = '@';
+
+/// keywords of ssh_config(5) that can be set in a Host block
+const SSH_OPTIONS: &[&str] = &[
+    "AddKeysToAgent", "AddressFamily", "BatchMode", "BindAddress", "BindInterface", "CanonicalDomains",
+    "CanonicalizeFallbackLocal", "CanonicalizeHostname", "CanonicalizeMaxDots", "CanonicalizePermittedCNAMEs",
+    "CASignatureAlgorithms", "CertificateFile", "ChallengeResponseAuthentication", "ChannelTimeout", "CheckHostIP",
+    "Ciphers", "ClearAllForwardings", "Compression", "ConnectionAttempts", "ConnectTimeout", "ControlMaster",
+    "ControlPath", "ControlPersist", "DynamicForward", "EnableEscapeCommandline", "EnableSSHKeysign", "EscapeChar",
+    "ExitOnForwardFailure", "FingerprintHash", "ForkAfterAuthentication", "ForwardAgent", "ForwardX11",
+    "ForwardX11Timeout", "ForwardX11Trusted", "GatewayPorts", "GlobalKnownHostsFile", "GSSAPIAuthentication",
+    "GSSAPIDelegateCredentials", "HashKnownHosts", "HostbasedAcceptedAlgorithms", "HostbasedAuthentication",
+    "HostbasedKeyTypes", "HostKeyAlgorithms", "HostKeyAlias", "HostName", "IdentitiesOnly", "IdentityAgent",
+    "IdentityFile", "IgnoreUnknown", "Include", "IPQoS", "KbdInteractiveAuthentication", "KbdInteractiveDevices",
+    "KexAlgorithms", "KnownHostsCommand", "LocalCommand", "LocalForward", "LogLevel", "LogVerbose", "MACs",
+    "NoHostAuthenticationForLocalhost", "NumberOfPasswordPrompts", "ObscureKeystrokeTiming",
+    "PasswordAuthentication", "PermitLocalCommand", "PermitRemoteOpen", "PKCS11Provider", "Port",
+    "PreferredAuthentications", "ProxyCommand", "ProxyJump", "ProxyUseFdpass", "PubkeyAcceptedAlgorithms",
+    "PubkeyAcceptedKeyTypes", "PubkeyAuthentication", "RekeyLimit", "RemoteCommand", "RemoteForward", "RequestTTY",
+    "RequiredRSASize", "RevokedHostKeys", "SecurityKeyProvider", "SendEnv", "ServerAliveCountMax",
+    "ServerAliveInterval", "SessionType", "SetEnv", "StdinNull", "StreamLocalBindMask", "StreamLocalBindUnlink",
+    "StrictHostKeyChecking", "SyslogFacility", "Tag", "TCPKeepAlive", "Tunnel", "TunnelDevice", "UpdateHostKeys",
+    "UseKeychain", "User", "UserKnownHostsFile", "VerifyHostKeyDNS", "VisualHostKey", "XAuthLocation",
+];
+
+#[derive(Subcommand, Debug)]
+pub enum ExportListCommand {
+    /// writes every option of the connections matching the filter as CSV to stdout, one column for each option
+    Csv {
+        /// Optional filter for the connections, "tag:<Tag>" exports the connections with the tag
+        #[clap(value_parser, value_name = "Filter")]
+        filter: Option<String>,
+    },
+    /// writes every option of the connections matching the filter as a JSON list to stdout
+    Json {
+        /// Optional filter for the connections, "tag:<Tag>" exports the connections with the tag
+        #[clap(value_parser, value_name = "Filter")]
+        filter: Option<String>,
+    },
+}
+
+/// Host of a CSV or JSON list, the options and "@<key>" structured comments in the order they are written
+pub type HostRecord = Vec<(String, String)>;
+
+/// the Host line, the options and the structured comments of the block, the options can be repeated
+pub fn host_record(host: &SshHost) -> HostRecord {
+    let mut record = vec![(String::from(HOST), host.name.clone())];
+    record.extend(host.options.iter().cloned());
+    record.extend(
+        host.annotations
+            .iter()
+            .map(|(key, value)| (format!("{ANNOTATION_PREFIX}{key}"), value.clone())),
+    );
+    record
+}
+
+/// the columns of the records in the order they first appear, "Host" first
+fn columns(records: &[HostRecord]) -> Vec<String> {
+    let mut columns: Vec<String> = vec![String::from(HOST)];
+    for (key, _) in records.iter().flatten() {
+        if !columns.iter().any(|column| column.eq_ignore_ascii_case(key)) {
+            columns.push(key.clone());
+        }
+    }
+    columns
+}
+
+/// the values of the record for the column, the keywords are case insensitive as in ssh
+fn values<'a>(record: &'a HostRecord, column: &str) -> Vec<&'a str> {
+    record
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case(column))
+        .map(|(_, value)| value.as_str())
+        .collect()
+}
+
+/// writes a CSV with a column for each option, repeated options have their values in separate lines of the cell
+pub fn write_csv(records: &[HostRecord]) -> Result<String, Box<dyn Error>> {
+    let columns = columns(records);
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&columns)?;
+    for record in records {
+        writer.write_record(columns.iter().map(|column| values(record, column).join("\n")))?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// writes a JSON list with an object for each host, repeated options are lists
+pub fn write_json(records: &[HostRecord]) -> Result<String, Box<dyn Error>> {
+    let list = records
+        .iter()
+        .map(|record| {
+            let mut object = Map::new();
+            for column in columns(std::slice::from_ref(record)) {
+                let values = values(record, column.as_str());
+                let value = match values.as_slice() {
+                    [value] => Value::from(*value),
+                    values => Value::from(values.to_vec()),
+                };
+                object.insert(column, value);
+            }
+            Value::Object(object)
+        })
+        .collect::<Vec<Value>>();
+    Ok(serde_json::to_string_pretty(&list)?)
+}
+
+/// the column a "<Column>=<Option>" mapping gives to a column of the file, an empty option ignores the column
+fn map_column(column: &str, mapping: &[(String, String)]) -> String {
+    mapping
+        .iter()
+        .find(|(from, _)| from.eq_ignore_ascii_case(column.trim()))
+        .map(|(_, to)| to.clone())
+        .unwrap_or(column.trim().to_string())
+}
+
+/// splits the "<Column>=<Option>" mappings of the command line
+pub fn parse_mapping(mappings: &[String]) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    mappings
+        .iter()
+        .map(|mapping| match mapping.split_once('=') {
+            Some((column, option)) => Ok((column.trim().to_string(), option.trim().to_string())),
+            None => Err(format!("\"{mapping}\" is not a valid mapping, use <Column>=<Option>").into()),
+        })
+        .collect()
+}
+
+/// reads the rows of a CSV with a header, the lines of a cell are repeated values of the option
+pub fn read_csv(data: &str, mapping: &[(String, String)]) -> Result<Vec<HostRecord>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data.as_bytes());
+    let columns = reader
+        .headers()?
+        .iter()
+        .map(|column| map_column(column, mapping))
+        .collect::<Vec<String>>();
+    let mut records = vec![];
+    for row in reader.records() {
+        let row = row?;
+        let mut record = HostRecord::new();
+        for (column, cell) in columns.iter().zip(row.iter()) {
+            if column.is_empty() {
+                continue;
+            }
+            let values = cell.lines().map(|value| value.trim()).filter(|value| !value.is_empty());
+            // an empty cell is kept so the option is removed from the host when it is updated
+            let values = values.map(|value| value.to_string()).collect::<Vec<String>>();
+            match values.is_empty() {
+                true => record.push((column.clone(), String::new())),
+                false => record.extend(values.into_iter().map(|value| (column.clone(), value))),
+            }
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// reads a JSON list of objects, the lists are repeated values of the option
+pub fn read_json(data: &str, mapping: &[(String, String)]) -> Result<Vec<HostRecord>, Box<dyn Error>> {
+    let list: Vec<Map<String, Value>> = serde_json::from_str(data)?;
+    Ok(list
+        .iter()
+        .map(|object| {
+            let mut record = HostRecord::new();
+            for (key, value) in object.iter() {
+                let column = map_column(key, mapping);
+                if column.is_empty() {
+                    continue;
+                }
+                let values = match value {
+                    Value::Array(values) => values.iter().collect::<Vec<&Value>>(),
+                    value => vec![value],
+                };
+                // null and empty values are kept so the option is removed from the host when it is updated
+                let values = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::String(value) => value.trim().to_string(),
+                        Value::Null => String::new(),
+                        value => value.to_string(),
+                    })
+                    .filter(|value| !value.is_empty())
+                    .collect::<Vec<String>>();
+                match values.is_empty() {
+                    true => record.push((column.clone(), String::new())),
+                    false => record.extend(values.into_iter().map(|value| (column.clone(), value))),
+                }
+            }
+            record
+        })
+        .collect())
+}
+
+/// the key of a structured comment without the prefix, none for the options
+pub fn annotation_key(key: &str) -> Option<&str> {
+    key.strip_prefix(ANNOTATION_PREFIX)
+}
+
+/// checks if importing the record leaves the host as it is, only the keys of the record are compared
+pub fn record_matches(record: &HostRecord, host: &SshHost) -> bool {
+    let current = host_record(host);
+    record.iter().all(|(key, _)| {
+        let imported = values(record, key).into_iter().filter(|value| !value.is_empty()).collect::<Vec<&str>>();
+        imported == values(&current, key)
+    })
+}
+
+/// checks the record and turns it into a Host block, the errors are every problem found in the record,
+/// the keys of the record are returned too as they are the options and structured comments it sets or removes
+pub fn record_host(record: &HostRecord) -> Result<(ImportedHost, Vec<String>), Vec<String>> {
+    let mut errors = vec![];
+    let name = values(record, HOST).first().map(|name| name.to_string()).unwrap_or_default();
+    if name.is_empty() {
+        errors.push(String::from("it has no Host"));
+    } else if name.contains('#') {
+        errors.push(format!("\"{name}\" is not a valid Host"));
+    }
+    let mut options = vec![];
+    let mut annotations = vec![];
+    let mut keys: Vec<String> = vec![];
+    for (key, value) in record.iter().filter(|(key, _)| !key.eq_ignore_ascii_case(HOST)) {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+        if value.is_empty() {
+            continue;
+        }
+        if let Some(annotation) = key.strip_prefix(ANNOTATION_PREFIX) {
+            annotations.push((annotation.to_string(), value.clone()));
+            continue;
+        }
+        match SSH_OPTIONS.iter().find(|option| option.eq_ignore_ascii_case(key)) {
+            None => errors.push(format!("\"{key}\" is not a ssh_config option")),
+            Some(_) if key.eq_ignore_ascii_case("Port") && !value.parse::<u16>().is_ok_and(|port| port > 0) => {
+                errors.push(format!("the Port \"{value}\" is not a valid port"))
+            }
+            Some(_) if value.contains(['\n', '\r']) => errors.push(format!("the value of {key} has several lines")),
+            Some(_) => options.push((key.clone(), value.clone())),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let host = ImportedHost {
+        name,
+        options,
+        tags: vec![],
+        annotations,
+    };
+    Ok((host, keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(record: &HostRecord) -> Vec<(&str, &str)> {
+        record.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect()
+    }
+
+    #[test]
+    fn read_csv_with_mapping() {
+        let mapping = parse_mapping(&[String::from("Name=Host"), String::from("Owner=")]).unwrap();
+        let records = read_csv(
+            "Name,HostName,Owner,IdentityFile,Port\nweb,10.0.0.1,ops,\"~/.ssh/a\n~/.ssh/b\",\n",
+            &mapping,
+        )
+        .unwrap();
+        assert_eq!(
+            pairs(&records[0]),
+            [
+                ("Host", "web"),
+                ("HostName", "10.0.0.1"),
+                ("IdentityFile", "~/.ssh/a"),
+                ("IdentityFile", "~/.ssh/b"),
+                ("Port", ""),
+            ]
+        );
+        assert!(parse_mapping(&[String::from("Name")]).is_err());
+    }
+
+    #[test]
+    fn read_json_lists_and_nulls() {
+        let json = r#"[{"Host": "db", "Port": 2222, "LocalForward": ["1 h:1", "2 h:2"], "User": null}]"#;
+        let records = read_json(json, &[]).unwrap();
+        assert_eq!(
+            pairs(&records[0]),
+            [("Host", "db"), ("Port", "2222"), ("LocalForward", "1 h:1"), ("LocalForward", "2 h:2"), ("User", "")]
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let host = SshHost {
+            index: 0,
+            name: String::from("web"),
+            options: vec![
+                (String::from("HostName"), String::from("10.0.0.1")),
+                (String::from("LocalForward"), String::from("1 h:1")),
+                (String::from("LocalForward"), String::from("2 h:2")),
+            ],
+            annotations: vec![(String::from("tags"), String::from("prod"))],
+        };
+        let csv = write_csv(&[host_record(&host)]).unwrap();
+        let records = read_csv(csv.as_str(), &[]).unwrap();
+        assert_eq!(records, [host_record(&host)]);
+        assert!(record_matches(&records[0], &host));
+        let (imported, keys) = record_host(&records[0]).unwrap();
+        assert_eq!(imported.options, host.options);
+        assert_eq!(imported.annotations, host.annotations);
+        assert_eq!(keys, ["HostName", "LocalForward", "@tags"]);
+    }
+
+    #[test]
+    fn record_host_errors() {
+        let record = vec![
+            (String::from("Port"), String::from("0")),
+            (String::from("Colour"), String::from("red")),
+        ];
+        assert_eq!(
+            record_host(&record).err().unwrap(),
+            ["it has no Host", "the Port \"0\" is not a valid port", "\"Colour\" is not a ssh_config option"]
+        );
+    }
+}
@@ -0,0 +1,728 @@
+use crate::error::CommandError;
+use crate::known_hosts::{is_address, parse_known_hosts, KnownHost};
+use crate::launch::LaunchMode;
+use crate::putty::{decode_export, parse_sessions, session_host};
+use crate::annotations::{annotation, block_lines};
+use crate::host_list::{annotation_key, parse_mapping, read_csv, read_json, record_host, record_matches};
+use crate::ansible::{host_options, parse_inventory, InventoryHost, IMPORTED_OPTIONS};
+use crate::ssh_config::{
+    effective_address, is_pattern, option_line, parse_ssh_hosts, read_ssh_hosts, split_option, SshHost,
+};
+use crate::tags::{join_tags, split_tags};
+use clap::Subcommand;
+use std::error::Error;
+use std::fs::{read, read_to_string, write, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand, Debug)]
+pub enum ImportCommand {
+    /// adds the servers of the known_hosts file that aren't in the ssh config file, asking for each one unless "--all" is used
+    KnownHosts {
+        /// known_hosts file to read, ~/.ssh/known_hosts by default
+        #[clap(value_parser, value_name = "FILE")]
+        file: Option<PathBuf>,
+
+        /// Adds every server with the proposed name without asking
+        #[clap(short, long, value_parser, default_value_t = false)]
+        all: bool,
+
+        /// User to log in as in the added hosts
+        #[clap(short, long, value_parser, value_name = "User")]
+        user: Option<String>,
+    },
+    /// adds or updates the hosts of an Ansible INI or YAML inventory, the groups of the hosts are added as tags
+    Ansible {
+        /// Inventory file
+        #[clap(value_parser, value_name = "FILE")]
+        file: PathBuf,
+
+        /// Writes the changes without asking for confirmation
+        #[clap(short, long, value_parser, default_value_t = false)]
+        yes: bool,
+    },
+    /// adds or updates the hosts of a CSV file with a header, "Host" is the Host line and the other columns are
+    /// ssh_config options or "@<key>" structured comments, use "--map" for other column names
+    Csv {
+        /// CSV file
+        #[clap(value_parser, value_name = "FILE")]
+        file: PathBuf,
+
+        /// Column of the file and the option it sets, "<Column>=<Option>", an empty option ignores the column
+        #[clap(short, long = "map", value_parser, value_name = "mapping")]
+        mapping: Vec<String>,
+
+        /// Writes the changes without asking for confirmation
+        #[clap(short, long, value_parser, default_value_t = false)]
+        yes: bool,
+    },
+    /// adds or updates the hosts of a JSON list of objects, with the same keys as the columns of "import csv"
+    Json {
+        /// JSON file
+        #[clap(value_parser, value_name = "FILE")]
+        file: PathBuf,
+
+        /// Key of the objects and the option it sets, "<Key>=<Option>", an empty option ignores the key
+        #[clap(short, long = "map", value_parser, value_name = "mapping")]
+        mapping: Vec<String>,
+
+        /// Writes the changes without asking for confirmation
+        #[clap(short, long, value_parser, default_value_t = false)]
+        yes: bool,
+    },
+    /// adds the sessions of a PuTTY registry export, showing the hosts that will be added first
+    Putty {
+        /// .reg file exported from HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions
+        #[clap(value_parser, value_name = "FILE")]
+        file: PathBuf,
+
+        /// Adds the hosts without asking for confirmation
+        #[clap(short, long, value_parser, default_value_t = false)]
+        yes: bool,
+    },
+}
+
+/// Host block to add to the ssh config file
+#[derive(Debug, Clone)]
+pub struct ImportedHost {
+    pub name: String,
+    pub options: Vec<(String, String)>,
+    pub tags: Vec<String>,
+    /// other structured comments of ssh-view, written after the tags
+    pub annotations: Vec<(String, String)>,
+}
+
+impl ImportedHost {
+    /// the Host block as it is written in the ssh config file
+    pub fn block(&self) -> String {
+        let mut block = format!("Host {}\n", self.name);
+        if !self.tags.is_empty() {
+            block.push_str(format!("    #@tags {}\n", self.tags.join(",")).as_str());
+        }
+        for (key, value) in self.annotations.iter() {
+            block.push_str(format!("    #@{key} {value}").trim_end());
+            block.push('\n');
+        }
+        for (key, value) in self.options.iter() {
+            block.push_str(format!("    {}\n", option_line(key, value)).as_str());
+        }
+        block
+    }
+}
+
+/// appends the hosts at the end of the ssh config file, a dry run only shows them
+pub fn append_hosts(config_file: &Path, hosts: &[ImportedHost], launch: &LaunchMode) -> Result<(), Box<dyn Error>> {
+    if hosts.is_empty() {
+        println!("no hosts to add");
+        return Ok(());
+    }
+    if !launch.spawns() {
+        for host in hosts {
+            eprintln!("would add:\n{}", host.block());
+        }
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().append(true).open(config_file)?;
+    for host in hosts {
+        writeln!(file)?;
+        write!(file, "{}", host.block())?;
+    }
+    println!("{} hosts added to {}", hosts.len(), config_file.display());
+    Ok(())
+}
+
+/// shows the lines that will be added to the ssh config file and asks to confirm them
+pub fn confirm_hosts(config_file: &Path, hosts: &[ImportedHost]) -> Result<bool, Box<dyn Error>> {
+    println!("--- {}", config_file.display());
+    println!("+++ {}", config_file.display());
+    for host in hosts {
+        println!("+");
+        for line in host.block().lines() {
+            println!("+{line}");
+        }
+    }
+    println!("Type \"yes\" to add the {} hosts", hosts.len());
+    let mut response = String::new();
+    io::stdin().read_line(&mut response)?;
+    Ok(response.trim() == "yes")
+}
+
+/// replaces the options and the structured comments with the keys of the Host block with the index with the ones
+/// of the imported host, the rest of the block is kept
+pub fn update_block(data: &str, index: usize, host: &ImportedHost, options: &[&str], annotations: &[&str]) -> String {
+    let is_managed = |line: &str| match annotation(line) {
+        Some((key, _)) => annotations.contains(&key),
+        None => split_option(line).is_some_and(|(key, _)| options.iter().any(|option| option.eq_ignore_ascii_case(key.as_str()))),
+    };
+    let mut output = String::with_capacity(data.len());
+    let mut block = None;
+    for line in data.lines() {
+        if line.trim().starts_with("Host ") {
+            block = Some(block.map_or(0, |block| block + 1));
+            output.push_str(line);
+            output.push('\n');
+            if block == Some(index) {
+                host.block().lines().skip(1).for_each(|line| {
+                    output.push_str(line);
+                    output.push('\n');
+                });
+            }
+            continue;
+        }
+        if block == Some(index) && is_managed(line) {
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    if !data.ends_with('\n') {
+        output.pop();
+    }
+    output
+}
+
+/// shows the lines that change between the two versions of the ssh config file, with two lines around each change
+pub fn print_diff(config_file: &Path, old: &str, new: &str) {
+    let old = old.lines().collect::<Vec<&str>>();
+    let new = new.lines().collect::<Vec<&str>>();
+    // longest common subsequence of the lines after each position
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let mut lines: Vec<(char, &str)> = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    println!("--- {}", config_file.display());
+    println!("+++ {}", config_file.display());
+    let changed = |line: usize| lines.get(line).is_some_and(|(kind, _)| *kind != ' ');
+    let mut skipped = false;
+    for (position, (kind, line)) in lines.iter().enumerate() {
+        if (position.saturating_sub(2)..=position + 2).any(changed) {
+            if skipped {
+                println!("@@");
+                skipped = false;
+            }
+            println!("{kind}{line}");
+        } else {
+            skipped = true;
+        }
+    }
+}
+
+/// writes the new contents of the ssh config file after showing the changes and asking to confirm them,
+/// a dry run only shows the changes
+pub fn write_changes(config_file: &Path, old: &str, new: &str, yes: bool, launch: &LaunchMode) -> Result<bool, Box<dyn Error>> {
+    if old == new {
+        println!("{} is up to date", config_file.display());
+        return Ok(false);
+    }
+    print_diff(config_file, old, new);
+    if !launch.spawns() {
+        return Ok(false);
+    }
+    if !yes {
+        println!("Type \"yes\" to write the changes");
+        let mut response = String::new();
+        io::stdin().read_line(&mut response)?;
+        if response.trim() != "yes" {
+            println!("the config wasn't changed");
+            return Ok(false);
+        }
+    }
+    write(config_file, new)?;
+    Ok(true)
+}
+
+/// the connection names of the config file, used to propose names that are not taken
+pub fn aliases(hosts: &[SshHost]) -> Vec<String> {
+    hosts
+        .iter()
+        .filter(|host| !is_pattern(host.name.as_str()))
+        .flat_map(|host| host.name.split_whitespace().map(|name| name.to_string()))
+        .collect()
+}
+
+/// returns the name, or the name with a number after it if it's taken
+pub fn unique_alias(name: &str, taken: &[String]) -> String {
+    let mut alias = name.to_string();
+    let mut number = 2;
+    while taken.contains(&alias) {
+        alias = format!("{name}-{number}");
+        number += 1;
+    }
+    alias
+}
+
+/// checks if the name can be used as a connection name of the table
+pub fn validate_alias(name: &str, taken: &[String]) -> Result<(), Box<dyn Error>> {
+    let message = if name.is_empty() || name.contains(char::is_whitespace) || is_pattern(name) {
+        format!("\"{name}\" is not a valid name, names can't be empty or have spaces or patterns")
+    } else if name.parse::<usize>().is_ok() || name.starts_with('@') || name.starts_with("tag:") {
+        format!("\"{name}\" is not a valid name, it would be taken as an index, a pin or a tag filter")
+    } else if taken.iter().any(|alias| alias == name) {
+        format!("there is already a host named \"{name}\"")
+    } else {
+        return Ok(());
+    };
+    Err(Box::new(CommandError::new("Import", message.as_str())))
+}
+
+/// the first label of host names and "host-<Address>" for addresses, with the port when it isn't 22
+fn propose_alias(host: &KnownHost) -> String {
+    let name = host.host_name();
+    let name = match is_address(name) {
+        true => format!("host-{}", name.replace(['.', ':'], "-").trim_matches('-')),
+        false => name.split('.').next().unwrap_or(name).to_string(),
+    };
+    match host.port {
+        22 => name,
+        port => format!("{name}-{port}"),
+    }
+}
+
+/// checks if the ssh config file already connects to any of the names of the server
+fn is_configured(host: &KnownHost, ssh_hosts: &[SshHost], aliases: &[String]) -> bool {
+    aliases.iter().any(|alias| {
+        let (host_name, port) = effective_address(ssh_hosts, alias);
+        port == host.port && host.names.iter().any(|name| name.eq_ignore_ascii_case(host_name.as_str()))
+    })
+}
+
+enum Answer {
+    Add(String),
+    Skip,
+    Quit,
+}
+
+/// asks if the server is added, the answer can be a different name for it
+fn ask(host: &KnownHost, proposed: &str, taken: &[String]) -> Result<Answer, Box<dyn Error>> {
+    loop {
+        println!(
+            "{} (port {}) is not in the config, add it as \"{}\"? Type \"yes\", \"no\", \"quit\" or another name",
+            host.names.join(", "),
+            host.port,
+            proposed
+        );
+        let mut response = String::new();
+        if io::stdin().read_line(&mut response)? == 0 {
+            return Ok(Answer::Quit);
+        }
+        let response = response.trim();
+        match response.to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Answer::Add(proposed.to_string())),
+            "" | "n" | "no" => return Ok(Answer::Skip),
+            "q" | "quit" => return Ok(Answer::Quit),
+            _ => match validate_alias(response, taken) {
+                Ok(_) => return Ok(Answer::Add(response.to_string())),
+                Err(e) => eprintln!("{e}"),
+            },
+        }
+    }
+}
+
+/// adds the servers of the known_hosts file that the ssh config file doesn't connect to yet
+pub fn import_known_hosts(
+    config_file: &Path,
+    file: &Path,
+    user: &Option<String>,
+    all: bool,
+    launch: &LaunchMode,
+) -> Result<(), Box<dyn Error>> {
+    let known_hosts = parse_known_hosts(read_to_string(file)?.as_str());
+    if known_hosts.hashed > 0 {
+        eprintln!(
+            "{} entries are hashed and can't be imported, HashKnownHosts stores the names that way",
+            known_hosts.hashed
+        );
+    }
+    if known_hosts.skipped > 0 {
+        eprintln!("{} entries with patterns, markers or no key were skipped", known_hosts.skipped);
+    }
+    let ssh_hosts = read_ssh_hosts(config_file)?;
+    let mut taken = aliases(&ssh_hosts);
+    let configured = taken.clone();
+    let mut hosts = vec![];
+    for host in known_hosts
+        .hosts
+        .iter()
+        .filter(|host| !is_configured(host, &ssh_hosts, &configured))
+    {
+        let proposed = unique_alias(propose_alias(host).as_str(), &taken);
+        // a dry run or a print shows every host with the proposed name instead of waiting for the answers
+        let name = match all || !launch.spawns() {
+            true => proposed,
+            false => match ask(host, proposed.as_str(), &taken)? {
+                Answer::Add(name) => name,
+                Answer::Skip => continue,
+                Answer::Quit => break,
+            },
+        };
+        taken.push(name.clone());
+        let mut options = vec![(String::from("HostName"), host.host_name().to_string())];
+        if let Some(user) = user {
+            options.push((String::from("user"), user.clone()));
+        }
+        options.push((String::from("port"), host.port.to_string()));
+        hosts.push(ImportedHost {
+            name,
+            options,
+            tags: vec![],
+            annotations: vec![],
+        });
+    }
+    append_hosts(config_file, &hosts, launch)
+}
+
+/// adds the ssh sessions of the PuTTY export, the sessions with the name of a host of the config file are skipped
+pub fn import_putty(config_file: &Path, file: &Path, yes: bool, launch: &LaunchMode) -> Result<(), Box<dyn Error>> {
+    let sessions = parse_sessions(decode_export(read(file)?.as_slice()).as_str());
+    if sessions.is_empty() {
+        return Err(Box::new(CommandError::new(
+            "Import",
+            format!("there are no PuTTY sessions in {}", file.display()).as_str(),
+        )));
+    }
+    let mut taken = aliases(&read_ssh_hosts(config_file)?);
+    let mut hosts = vec![];
+    for session in sessions.iter() {
+        let name = session.name.split_whitespace().collect::<Vec<&str>>().join("-");
+        if taken.contains(&name) {
+            println!("skipping {name}, there is already a host with that name");
+            continue;
+        }
+        let host = validate_alias(name.as_str(), &taken)
+            .map_err(|e| e.to_string())
+            .and_then(|_| session_host(session, name.as_str()));
+        match host {
+            Ok((host, warnings)) => {
+                for warning in warnings {
+                    eprintln!("{name}: {warning}");
+                }
+                taken.push(name);
+                hosts.push(host);
+            }
+            Err(reason) => println!("skipping {}, {}", session.name, reason),
+        }
+    }
+    if !hosts.is_empty() && launch.spawns() && !yes && !confirm_hosts(config_file, &hosts)? {
+        println!("no hosts were added");
+        return Ok(());
+    }
+    append_hosts(config_file, &hosts, launch)
+}
+
+/// key of the structured comment with the groups of the hosts imported from an Ansible inventory, it marks the hosts
+/// that can be updated when the inventory is imported again
+const ANSIBLE: &str = "ansible";
+/// key of the structured comment with the options that came from "-o" in the ssh args of the inventory, they are
+/// removed from the host when they aren't in the inventory anymore
+const ANSIBLE_OPTIONS: &str = "ansible-options";
+
+/// adds the hosts of the inventory and updates the ones imported before, the hosts with the same name that weren't
+/// imported from an inventory are left as they are
+pub fn import_ansible(config_file: &Path, file: &Path, yes: bool, launch: &LaunchMode) -> Result<(), Box<dyn Error>> {
+    let inventory = parse_inventory(file, read_to_string(file)?.as_str())?;
+    let old = read_to_string(config_file)?;
+    let taken = aliases(&read_ssh_hosts(config_file)?);
+    let (new, added, changed) = merge_inventory(old.as_str(), taken, &inventory);
+    if write_changes(config_file, old.as_str(), new.as_str(), yes, launch)? {
+        println!("{} hosts added and {} updated in {}", added, changed, config_file.display());
+    }
+    Ok(())
+}
+
+/// returns the config file with the hosts of the inventory added or updated, with the number of hosts added and
+/// updated, the taken names are the ones of the config file and its included files
+fn merge_inventory(old: &str, mut taken: Vec<String>, inventory: &[InventoryHost]) -> (String, usize, usize) {
+    let blocks = parse_ssh_hosts(old);
+    let mut new = old.to_string();
+    let mut added = vec![];
+    let mut changed = 0;
+    for inventory_host in inventory.iter() {
+        let name = inventory_host.name.as_str();
+        let (options, warnings) = host_options(inventory_host);
+        for warning in warnings {
+            eprintln!("{name}: {warning}");
+        }
+        let mut host = ImportedHost {
+            name: name.to_string(),
+            options,
+            tags: inventory_host.groups.clone(),
+            annotations: vec![(ANSIBLE.to_string(), inventory_host.groups.join(","))],
+        };
+        let mut extra_options: Vec<&str> = vec![];
+        for (key, _) in host.options.iter() {
+            if !IMPORTED_OPTIONS.iter().chain(extra_options.iter()).any(|option| option.eq_ignore_ascii_case(key)) {
+                extra_options.push(key.as_str());
+            }
+        }
+        if !extra_options.is_empty() {
+            let extra_options = extra_options.join(",");
+            host.annotations.push((ANSIBLE_OPTIONS.to_string(), extra_options));
+        }
+        let block = blocks
+            .iter()
+            .find(|block| block.name.split_whitespace().any(|alias| alias == name));
+        match block {
+            Some(block) => {
+                let lines = block_lines(new.as_str(), block.index);
+                let imported = lines
+                    .iter()
+                    .find_map(|line| annotation(line).filter(|(key, _)| *key == ANSIBLE).map(|(_, groups)| groups));
+                let Some(imported) = imported else {
+                    println!("skipping {name}, there is already a host with that name that wasn't imported from an inventory");
+                    continue;
+                };
+                // the tags that were groups of the last import are replaced with the current groups
+                let old_groups = split_tags(imported);
+                let tags = lines
+                    .iter()
+                    .filter_map(|line| annotation(line).filter(|(key, _)| *key == "tags").map(|(_, tags)| tags))
+                    .flat_map(split_tags)
+                    .filter(|tag| !old_groups.contains(tag))
+                    .collect::<Vec<String>>();
+                host.tags = split_tags(join_tags(&[host.tags.join(",").as_str(), tags.join(",").as_str()]).as_str());
+                // the options of the last import that aren't in the inventory anymore are removed too
+                let mut options = IMPORTED_OPTIONS.to_vec();
+                options.extend(
+                    lines
+                        .iter()
+                        .filter_map(|line| annotation(line).filter(|(key, _)| *key == ANSIBLE_OPTIONS))
+                        .flat_map(|(_, keys)| keys.split(',')),
+                );
+                options.extend(host.options.iter().map(|(key, _)| key.as_str()));
+                let annotations = ["tags", ANSIBLE, ANSIBLE_OPTIONS];
+                let updated = update_block(new.as_str(), block.index, &host, &options, &annotations);
+                if updated != new {
+                    changed += 1;
+                    new = updated;
+                }
+            }
+            None if taken.iter().any(|alias| alias == name) => {
+                println!("skipping {name}, there is already a host with that name in an included file");
+            }
+            None => match validate_alias(name, &taken) {
+                Ok(_) => {
+                    taken.push(name.to_string());
+                    added.push(host);
+                }
+                Err(e) => println!("skipping {name}, {e}"),
+            },
+        }
+    }
+    for host in added.iter() {
+        if !new.is_empty() && !new.ends_with('\n') {
+            new.push('\n');
+        }
+        new.push('\n');
+        new.push_str(host.block().as_str());
+    }
+    (new, added.len(), changed)
+}
+
+/// Format of the host lists of "import csv" and "import json"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostListFormat {
+    Csv,
+    Json,
+}
+
+/// adds the hosts of the list and replaces the options and structured comments of the hosts of the config file with
+/// the same Host line, the rows with errors are reported and skipped
+pub fn import_host_list(
+    config_file: &Path,
+    file: &Path,
+    format: HostListFormat,
+    mapping: &[String],
+    yes: bool,
+    launch: &LaunchMode,
+) -> Result<(), Box<dyn Error>> {
+    let mapping = parse_mapping(mapping)?;
+    let data = read_to_string(file)?;
+    let (records, row_name, first_row) = match format {
+        HostListFormat::Csv => (read_csv(data.as_str(), &mapping)?, "row", 2),
+        HostListFormat::Json => (read_json(data.as_str(), &mapping)?, "entry", 1),
+    };
+    let old = read_to_string(config_file)?;
+    let blocks = parse_ssh_hosts(old.as_str());
+    let included = read_ssh_hosts(config_file)?
+        .into_iter()
+        .filter(|host| host.index >= blocks.len())
+        .collect::<Vec<SshHost>>();
+    let mut new = old.clone();
+    let mut added: Vec<ImportedHost> = vec![];
+    let (mut changed, mut failed) = (0, 0);
+    for (row, record) in records.iter().enumerate().filter(|(_, record)| !record.is_empty()) {
+        let (host, keys) = match record_host(record) {
+            Ok(host) => host,
+            Err(errors) => {
+                eprintln!("{} {}: {}", row_name, row + first_row, errors.join(", "));
+                failed += 1;
+                continue;
+            }
+        };
+        // the export lists the hosts of the included files too, they are only reported when the list changes them
+        if let Some(included_host) = included.iter().find(|included_host| included_host.name == host.name) {
+            if !record_matches(record, included_host) {
+                eprintln!("{} {}: {} is in an included file, it can't be changed", row_name, row + first_row, host.name);
+                failed += 1;
+            }
+            continue;
+        }
+        if added.iter().any(|other| other.name == host.name) {
+            eprintln!("{} {}: {} is repeated", row_name, row + first_row, host.name);
+            failed += 1;
+            continue;
+        }
+        match blocks.iter().find(|block| block.name == host.name) {
+            Some(block) => {
+                let annotations = keys.iter().filter_map(|key| annotation_key(key)).collect::<Vec<&str>>();
+                let options = keys
+                    .iter()
+                    .filter(|key| annotation_key(key).is_none())
+                    .map(|key| key.as_str())
+                    .collect::<Vec<&str>>();
+                let updated = update_block(new.as_str(), block.index, &host, &options, &annotations);
+                if updated != new {
+                    changed += 1;
+                    new = updated;
+                }
+            }
+            None => added.push(host),
+        }
+    }
+    if failed > 0 {
+        eprintln!("skipped because of errors: {failed}");
+    }
+    for host in added.iter() {
+        if !new.is_empty() && !new.ends_with('\n') {
+            new.push('\n');
+        }
+        new.push('\n');
+        new.push_str(host.block().as_str());
+    }
+    if write_changes(config_file, old.as_str(), new.as_str(), yes, launch)? {
+        println!("{} hosts added and {} updated in {}", added.len(), changed, config_file.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory(data: &str) -> Vec<InventoryHost> {
+        parse_inventory(Path::new("hosts.ini"), data).unwrap()
+    }
+
+    fn merge(old: &str, data: &str) -> (String, usize, usize) {
+        let taken = aliases(&parse_ssh_hosts(old));
+        merge_inventory(old, taken, &inventory(data))
+    }
+
+    #[test]
+    fn import_ansible_adds_new_hosts() {
+        let (new, added, changed) = merge(
+            "Host bastion\n    HostName 10.0.0.254\n",
+            "[web]\nweb1 ansible_host=10.0.0.1 ansible_ssh_common_args='-o ForwardAgent=yes'\n",
+        );
+        assert_eq!((added, changed), (1, 0));
+        assert_eq!(
+            new,
+            "Host bastion\n    HostName 10.0.0.254\n\n\
+             Host web1\n    #@tags web\n    #@ansible web\n    #@ansible-options ForwardAgent\n    \
+             HostName 10.0.0.1\n    ForwardAgent yes\n"
+        );
+    }
+
+    #[test]
+    fn import_ansible_again_updates_the_imported_hosts() {
+        let (imported, _, _) = merge(
+            "",
+            "[web]\nweb1 ansible_host=10.0.0.1 ansible_ssh_common_args='-o ForwardAgent=yes'\n",
+        );
+        // the tags added by hand are kept, the ones of the groups the host left are removed
+        let edited = imported.replace("#@tags web", "#@tags web,critical") + "    ServerAliveInterval 30\n";
+        let (new, added, changed) = merge(edited.as_str(), "[app]\nweb1 ansible_host=10.0.0.2 ansible_user=deploy\n");
+        assert_eq!((added, changed), (0, 1));
+        assert_eq!(
+            new,
+            "\nHost web1\n    #@tags app,critical\n    #@ansible app\n    HostName 10.0.0.2\n    user deploy\n    \
+             ServerAliveInterval 30\n"
+        );
+        // importing the same inventory again changes nothing
+        let (again, _, changed) = merge(new.as_str(), "[app]\nweb1 ansible_host=10.0.0.2 ansible_user=deploy\n");
+        assert_eq!((again, changed), (new, 0));
+    }
+
+    #[test]
+    fn import_ansible_skips_the_hosts_not_imported_before() {
+        let old = "Host db db.prod\n    HostName 10.0.0.5\n    #@tags prod\n";
+        let (new, added, changed) = merge(old, "[database]\ndb ansible_host=10.0.0.9\n");
+        assert_eq!((new.as_str(), added, changed), (old, 0, 0));
+    }
+
+    fn known_host(names: &[&str], port: u16) -> KnownHost {
+        KnownHost {
+            names: names.iter().map(|name| name.to_string()).collect(),
+            port,
+        }
+    }
+
+    #[test]
+    fn propose_aliases_for_known_hosts() {
+        assert_eq!(propose_alias(&known_host(&["web1.example.com", "10.0.0.1"], 22)), "web1");
+        assert_eq!(propose_alias(&known_host(&["web1.example.com"], 2222)), "web1-2222");
+        assert_eq!(propose_alias(&known_host(&["10.0.0.1"], 22)), "host-10-0-0-1");
+        assert_eq!(propose_alias(&known_host(&["fe80::1"], 2200)), "host-fe80--1-2200");
+    }
+
+    #[test]
+    fn unique_alias_adds_a_number_when_taken() {
+        let taken = vec![String::from("web"), String::from("web-2"), String::from("db")];
+        assert_eq!(unique_alias("web", &taken), "web-3");
+        assert_eq!(unique_alias("db", &taken), "db-2");
+        assert_eq!(unique_alias("cache", &taken), "cache");
+    }
+
+    #[test]
+    fn validate_aliases() {
+        let taken = vec![String::from("web")];
+        assert!(validate_alias("web-2", &taken).is_ok());
+        for name in ["", "web 2", "web*", "db?", "!web", "12", "@1", "tag:prod", "web"] {
+            assert!(validate_alias(name, &taken).is_err(), "{name} should be rejected");
+        }
+    }
+
+    #[test]
+    fn known_hosts_are_configured_by_host_name_or_alias() {
+        let ssh_hosts = parse_ssh_hosts(
+            "Host web\n    HostName 10.0.0.1\n\nHost db.example.com\n    Port 2222\n\nHost *\n    User admin\n",
+        );
+        let aliases = aliases(&ssh_hosts);
+        assert!(is_configured(&known_host(&["web.example.com", "10.0.0.1"], 22), &ssh_hosts, &aliases));
+        assert!(is_configured(&known_host(&["DB.example.com"], 2222), &ssh_hosts, &aliases));
+        // the same server on another port is a different one
+        assert!(!is_configured(&known_host(&["db.example.com"], 22), &ssh_hosts, &aliases));
+        assert!(!is_configured(&known_host(&["cache.example.com", "10.0.0.3"], 22), &ssh_hosts, &aliases));
+    }
+}
@@ -0,0 +1,139 @@
+use std::net::IpAddr;
+
+/// Server of the known_hosts file, with every name it was reached with on the same port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownHost {
+    /// host names first and then the addresses
+    pub names: Vec<String>,
+    pub port: u16,
+}
+
+impl KnownHost {
+    pub fn host_name(&self) -> &str {
+        self.names[0].as_str()
+    }
+}
+
+/// Servers of the known_hosts file and the lines that couldn't be used
+#[derive(Debug, Default)]
+pub struct KnownHosts {
+    pub hosts: Vec<KnownHost>,
+    /// lines with hashed names, they can't be turned back into the names
+    pub hashed: usize,
+    /// lines with patterns, "@cert-authority" and "@revoked" markers or without a key
+    pub skipped: usize,
+}
+
+/// parses the lines of a known_hosts file, "<Names> <KeyType> <Key>" with an optional marker before the names,
+/// the names are separated by commas and the ones with other port than 22 are written as "[<Host>]:<Port>"
+pub fn parse_known_hosts(data: &str) -> KnownHosts {
+    let mut known_hosts = KnownHosts::default();
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace().collect::<Vec<&str>>();
+        // the markers are for certificate authorities and revoked keys, neither of them is a server to connect to
+        if fields[0].starts_with('@') || fields.len() < 3 {
+            known_hosts.skipped += 1;
+            continue;
+        }
+        let names = fields.remove(0);
+        if names.starts_with("|1|") {
+            known_hosts.hashed += 1;
+            continue;
+        }
+        let mut line_hosts: Vec<KnownHost> = vec![];
+        for name in names.split(',') {
+            if name.is_empty() || name.contains(['*', '?', '!']) {
+                continue;
+            }
+            let (name, port) = split_name(name);
+            match line_hosts.iter_mut().find(|host| host.port == port) {
+                Some(host) => host.names.push(name),
+                None => line_hosts.push(KnownHost { names: vec![name], port }),
+            }
+        }
+        if line_hosts.is_empty() {
+            known_hosts.skipped += 1;
+        }
+        for mut host in line_hosts {
+            // a server has a line for each type of key, all of them are the same host
+            match known_hosts
+                .hosts
+                .iter_mut()
+                .find(|known| known.port == host.port && known.names.iter().any(|name| host.names.contains(name)))
+            {
+                Some(known) => {
+                    for name in host.names {
+                        if !known.names.contains(&name) {
+                            known.names.push(name);
+                        }
+                    }
+                    known.names.sort_by_key(|name| is_address(name));
+                }
+                None => {
+                    host.names.sort_by_key(|name| is_address(name));
+                    known_hosts.hosts.push(host);
+                }
+            }
+        }
+    }
+    known_hosts
+}
+
+/// splits "[<Host>]:<Port>" names, the names without brackets use port 22
+fn split_name(name: &str) -> (String, u16) {
+    let bracketed = name
+        .strip_prefix('[')
+        .and_then(|name| name.split_once("]:"))
+        .and_then(|(host, port)| port.parse::<u16>().ok().map(|port| (host.to_lowercase(), port)));
+    bracketed.unwrap_or((name.to_lowercase(), 22))
+}
+
+pub fn is_address(name: &str) -> bool {
+    name.parse::<IpAddr>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_known_hosts_groups_the_names_of_a_server() {
+        let known_hosts = parse_known_hosts(
+            "# comment\n\
+             10.0.0.1,Web.example.com ssh-ed25519 AAAA\n\
+             web.example.com ssh-rsa AAAA\n\
+             [git.example.com]:2222,[10.0.0.2]:2222 ssh-ed25519 AAAA\n",
+        );
+        assert_eq!(
+            known_hosts.hosts,
+            [
+                KnownHost {
+                    names: vec![String::from("web.example.com"), String::from("10.0.0.1")],
+                    port: 22,
+                },
+                KnownHost {
+                    names: vec![String::from("git.example.com"), String::from("10.0.0.2")],
+                    port: 2222,
+                },
+            ]
+        );
+        assert_eq!(known_hosts.hosts[0].host_name(), "web.example.com");
+    }
+
+    #[test]
+    fn parse_known_hosts_skips_what_isnt_a_server() {
+        let known_hosts = parse_known_hosts(
+            "|1|c2FsdA==|aGFzaA== ssh-ed25519 AAAA\n\
+             @cert-authority *.example.com ssh-ed25519 AAAA\n\
+             *.example.com ssh-ed25519 AAAA\n\
+             nokey.example.com\n",
+        );
+        assert!(known_hosts.hosts.is_empty());
+        assert_eq!(known_hosts.hashed, 1);
+        assert_eq!(known_hosts.skipped, 3);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::ssh_config::{effective_option, SshHost};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// What to do with the commands built by the subcommands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// runs the command
+    Spawn,
+    /// prints the command that would be run to stderr without running it
+    DryRun,
+    /// writes the command to stdout instead of running it
+    Print,
+}
+
+impl LaunchMode {
+    pub fn new(dry_run: bool, print: bool) -> Self {
+        if dry_run {
+            LaunchMode::DryRun
+        } else if print {
+            LaunchMode::Print
+        } else {
+            LaunchMode::Spawn
+        }
+    }
+
+    pub fn spawns(&self) -> bool {
+        *self == LaunchMode::Spawn
+    }
+
+    /// shows the command when it is not spawned, returns true if the caller must spawn it
+    pub fn announce(&self, command: &Command) -> bool {
+        match self {
+            LaunchMode::Spawn => return true,
+            LaunchMode::DryRun => eprintln!("would run: {}", command_line(command)),
+            LaunchMode::Print => println!("{}", command_line(command)),
+        }
+        false
+    }
+
+    /// runs the command and waits for it, or only shows it
+    pub fn run(&self, command: &mut Command) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+        if !self.announce(command) {
+            return Ok(None);
+        }
+        Ok(Some(command.spawn()?.wait()?))
+    }
+}
+
+/// Shell the exported commands are quoted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Posix,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    /// quotes the argument, leaving it as is when it has no special characters for the shell
+    pub fn quote(&self, arg: &str) -> String {
+        let special = match self {
+            Shell::Posix => "",
+            Shell::Fish => "%",
+            Shell::Powershell => "@%,",
+        };
+        let safe = |c: char| c.is_ascii_alphanumeric() || ("@%+=:,./-_".contains(c) && !special.contains(c));
+        if !arg.is_empty() && arg.chars().all(safe) {
+            return arg.to_string();
+        }
+        match self {
+            Shell::Posix => format!("'{}'", arg.replace('\'', r"'\''")),
+            Shell::Fish => format!("'{}'", arg.replace('\\', r"\\").replace('\'', r"\'")),
+            Shell::Powershell => format!("'{}'", arg.replace('\'', "''")),
+        }
+    }
+
+    /// returns the command line of the command with every argument quoted
+    pub fn command_line(&self, command: &Command) -> String {
+        let program = self.quote(command.get_program().to_string_lossy().as_ref());
+        // powershell takes a quoted program as a string unless it is called with "&"
+        let program = match (self, program.starts_with('\'')) {
+            (Shell::Powershell, true) => format!("& {program}"),
+            _ => program,
+        };
+        std::iter::once(program)
+            .chain(command.get_args().map(|arg| self.quote(arg.to_string_lossy().as_ref())))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+/// returns the command line of the command with every argument quoted for POSIX shells
+pub fn command_line(command: &Command) -> String {
+    Shell::Posix.command_line(command)
+}
+
+/// Program "use" connects to the host with, stored in the ssh-view metadata for each host
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Launcher {
+    #[default]
+    Ssh,
+    Mosh,
+    Et,
+}
+
+impl Launcher {
+    pub fn program(&self) -> &'static str {
+        match self {
+            Launcher::Ssh => "ssh",
+            Launcher::Mosh => "mosh",
+            Launcher::Et => "et",
+        }
+    }
+
+    /// builds the command that connects to the first alias of the host, mosh runs ssh with the config file given
+    /// to ssh-view with --config, without it ssh reads the default config files
+    pub fn command(
+        &self,
+        connection_name: &str,
+        args: &Option<Vec<String>>,
+        config_file: Option<&Path>,
+        hosts: &[SshHost],
+    ) -> Command {
+        // the Host line may have several names, the options are resolved for the first one as ssh would for that name
+        let alias = connection_name.split_whitespace().next().unwrap_or_default();
+        let mut command = Command::new(self.program());
+        if *self == Launcher::Mosh {
+            if let Some(config_file) = config_file {
+                let config_file = Shell::Posix.quote(config_file.to_string_lossy().as_ref());
+                command.arg(format!("--ssh=ssh -F {config_file}"));
+            }
+            // mosh finds the address of the server with its own ProxyCommand, which replaces the ProxyJump of the host,
+            // so the server is asked for its address instead
+            let proxy_jump = effective_option(hosts, alias, "ProxyJump");
+            if proxy_jump.is_some_and(|proxy_jump| !proxy_jump.eq_ignore_ascii_case("none")) {
+                command.arg("--experimental-remote-ip=remote");
+            }
+        }
+        command
+            .arg(alias)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        if let Some(args) = args {
+            command.args(args.iter());
+        }
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh_config::parse_ssh_hosts;
+
+    #[test]
+    fn quote_leaves_plain_arguments() {
+        assert_eq!(Shell::Posix.quote("user@host:/tmp/file-1.txt"), "user@host:/tmp/file-1.txt");
+        assert_eq!(Shell::Fish.quote("-L8080:localhost:80"), "-L8080:localhost:80");
+    }
+
+    #[test]
+    fn quote_posix() {
+        assert_eq!(Shell::Posix.quote(""), "''");
+        assert_eq!(Shell::Posix.quote("a b"), "'a b'");
+        assert_eq!(Shell::Posix.quote("it's"), r"'it'\''s'");
+        assert_eq!(Shell::Posix.quote("$HOME"), "'$HOME'");
+        assert_eq!(Shell::Posix.quote("~/.ssh/id"), "'~/.ssh/id'");
+        assert_eq!(Shell::Posix.quote("--key=~/id"), "'--key=~/id'");
+    }
+
+    #[test]
+    fn quote_fish() {
+        assert_eq!(Shell::Fish.quote("100%"), "'100%'");
+        assert_eq!(Shell::Fish.quote(r"it's a\b"), r"'it\'s a\\b'");
+    }
+
+    #[test]
+    fn quote_powershell() {
+        assert_eq!(Shell::Powershell.quote("user@host"), "'user@host'");
+        assert_eq!(Shell::Powershell.quote("a,b"), "'a,b'");
+        assert_eq!(Shell::Powershell.quote("it's"), "'it''s'");
+    }
+
+    #[test]
+    fn command_line_quotes_every_argument() {
+        let mut command = Command::new("ssh");
+        command.args(["-o", "ProxyCommand=nc %h %p", "web"]);
+        assert_eq!(Shell::Posix.command_line(&command), "ssh -o 'ProxyCommand=nc %h %p' web");
+        let command = Command::new("C:\\Program Files\\ssh.exe");
+        assert_eq!(Shell::Powershell.command_line(&command), "& 'C:\\Program Files\\ssh.exe'");
+    }
+
+    #[test]
+    fn mosh_asks_the_server_for_its_address_behind_a_proxy_jump() {
+        let hosts = parse_ssh_hosts("Host web web.prod\n    ProxyJump bastion\n\nHost db\n    ProxyJump none\n");
+        let args = Some(vec![String::from("--"), String::from("tmux")]);
+        let command = Launcher::Mosh.command("web web.prod", &args, Some(Path::new("/home/me/work config")), &hosts);
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["--ssh=ssh -F '/home/me/work config'", "--experimental-remote-ip=remote", "web", "--", "tmux"]
+        );
+        let command = Launcher::Mosh.command("db", &None, None, &hosts);
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["db"]);
+    }
+}
@@ -1,18 +1,62 @@
 extern crate core;
 
-use arboard::Clipboard;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCandidates;
+use clap_complete::env::CompleteEnv;
 use prettytable::{color, Attr, Cell, Row, Table};
 use regex::Regex;
 use std::error::Error;
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 use std::io::Write;
 use std::io::{BufRead, BufReader, BufWriter, Read};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::string::String;
 use std::{env, io};
-use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use ansible::InventoryFormat;
+use clipboard::ExportTarget;
+use completion::CompletionShell;
+use copy::{CopyBackend, CopyOptions, Location};
+use forward::{set_local_port, validate_forwards, Forward, LocalPort, PortRange};
+use history::Activity;
+use host_list::ExportListCommand;
+use import::{HostListFormat, ImportCommand};
+use launch::{LaunchMode, Launcher, Shell};
+use metadata::Metadata;
+use error::CommandError;
+use mount::MountOptions;
+use share::ExportAs;
+use tags::TagCommand;
+use tunnel::{Supervision, TunnelCommand, TunnelOptions, TunnelProfile};
+
+mod annotations;
+mod ansible;
+mod broadcast;
+mod browse;
+mod check;
+mod clipboard;
+mod completion;
+mod copy;
+mod error;
+mod exec;
+mod forward;
+mod history;
+mod host_list;
+mod import;
+mod known_hosts;
+mod launch;
+mod metadata;
+mod mount;
+mod notes;
+mod putty;
+mod share;
+mod ssh_config;
+mod state;
+mod tags;
+#[cfg(test)]
+mod test_support;
+mod tunnel;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -21,59 +65,125 @@ struct Cli {
     #[clap(short, long, value_parser, value_name = "FILE")]
     config: Option<PathBuf>,
 
+    /// Prints the commands that would be run to stderr without running them or changing any file
+    #[clap(long, value_parser, global = true, default_value_t = false)]
+    dry_run: bool,
+
+    /// Writes the commands to stdout instead of running them
+    #[clap(long, value_parser, global = true, default_value_t = false, conflicts_with = "dry_run")]
+    print: bool,
+
     /// Subcommand to use
     #[clap(subcommand)]
     command: Option<Commands>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct TunnelModeError {
-    message: String,
+/// Output format for the commands that print a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
 }
 
-impl TunnelModeError {
-    pub fn new(message: &str) -> Self {
-        TunnelModeError {
-            message: message.to_string()
-        }
-    }
-}
-
-impl Error for TunnelModeError {}
-
-impl Display for TunnelModeError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Tunnel Mode Error: {}", self.message)
-    }
+/// Order of the connections in the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortOrder {
+    /// the order of the ssh config file
+    Index,
+    Name,
+    Frecency,
 }
 
-
 #[derive(Subcommand)]
 enum Commands {
     /// Shows the current configuration
     Show {
-        /// Optional filter for the connections
-        filter: Option<String>
+        /// Optional filter for the connections, "tag:<Tag>" shows the connections with the tag
+        #[clap(add = ArgValueCandidates::new(completion::selection_candidates))]
+        filter: Option<String>,
+
+        /// Shows the description and the notes of the connections
+        #[clap(short, long, value_parser, default_value_t = false)]
+        details: bool,
+
+        /// Order of the connections, "frecency" shows first the ones used more often and more recently
+        #[clap(short, long, value_enum, value_name = "order", default_value_t = SortOrder::Index)]
+        sort: SortOrder,
     },
     /// launches the ssh command for the selected index of the table or the specified connection name in the table
     Use {
         /// Index of the selected connection
-        #[clap(value_parser, value_name = "Selection")]
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
         selection: String,
 
         /// Additional args to use in the command
         #[clap(short, long, value_parser, value_name = "args")]
         args: Option<Vec<String>>,
 
-        /// Command to use
-        #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("ssh"))]
-        command: String,
+        /// Command to use instead of the launcher of the host, see "launcher"
+        #[clap(short, long, value_parser, value_name = "command")]
+        command: Option<String>,
     },
-    /// exports the ssh command for the selected index of the table or the specified connection name in the table to the clipboard
-    Export {
+    /// manages the tags of the hosts, stored as "#@tags" comments in the ssh config file, use "tag:<Tag>" as a filter or selection
+    Tag {
+        #[clap(subcommand)]
+        action: TagCommand,
+    },
+    /// edits the description and the notes of the selected index of the table or the specified connection name in the table with $EDITOR, they are stored as "#@description" and "#@note" comments in the ssh config file
+    Note {
         /// Index of the selected connection
-        #[clap(value_parser, value_name = "Selection")]
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
         selection: String,
+    },
+    /// lists the connections used recently, or connects again to one of them
+    Recent {
+        /// Number of the connection in the list to connect to
+        #[clap(value_parser, value_name = "Number")]
+        number: Option<usize>,
+
+        /// Maximum number of connections listed
+        #[clap(short = 'n', long, value_parser, value_name = "limit", default_value_t = 10)]
+        limit: usize,
+
+        /// Additional args to use in the command
+        #[clap(short, long, value_parser, value_name = "args")]
+        args: Option<Vec<String>>,
+
+        /// Command to use instead of the launcher of the host, see "launcher"
+        #[clap(short, long, value_parser, value_name = "command")]
+        command: Option<String>,
+    },
+    /// pins the selected index of the table or the specified connection name in the table, pinned connections are shown first and can be selected with "@<Pin>"
+    Pin {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
+        selection: String,
+    },
+    /// unpins the selected index of the table or the specified connection name in the table
+    Unpin {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
+        selection: String,
+    },
+    /// shows or sets the program "use" connects with to the selected index of the table or the specified connection name in the table
+    Launcher {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
+        selection: String,
+
+        /// Program to connect with, "ssh" goes back to the default
+        #[clap(value_enum, value_name = "Launcher")]
+        launcher: Option<Launcher>,
+    },
+    /// exports the ssh command or the host definition for the selected index of the table or the specified connection name in the table to the clipboard, the terminal or stdout, "csv" and "json" write the options of every connection as a host list
+    #[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Export {
+        #[clap(subcommand)]
+        list: Option<ExportListCommand>,
+
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", required = true, add = ArgValueCandidates::new(completion::selection_candidates))]
+        selection: Option<String>,
 
         /// Additional args to use in the command
         #[clap(short, long, value_parser, value_name = "args")]
@@ -82,25 +192,128 @@ enum Commands {
         /// Command to use
         #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("ssh"))]
         command: String,
+
+        /// Shell to quote the command for
+        #[clap(short, long, value_enum, value_name = "shell", default_value_t = Shell::Posix)]
+        shell: Shell,
+
+        /// What to export, "config" and "command-expanded" can be used on machines without the alias
+        #[clap(long = "as", value_enum, value_name = "format", default_value_t = ExportAs::Command)]
+        export_as: ExportAs,
+
+        /// Where to send the command, "clipboard" falls back to "osc52" or "stdout" when there is no system clipboard
+        #[clap(short, long, value_enum, value_name = "target", default_value_t = ExportTarget::Clipboard)]
+        target: ExportTarget,
+    },
+    /// writes an inventory with the connections matching the filter to stdout, the tags of the connections are the groups
+    ExportInventory {
+        /// Optional filter for the connections, "tag:<Tag>" exports the connections with the tag
+        #[clap(add = ArgValueCandidates::new(completion::selection_candidates))]
+        filter: Option<String>,
+
+        /// Format of the inventory
+        #[clap(short, long, value_enum, value_name = "format", default_value_t = InventoryFormat::Ansible)]
+        format: InventoryFormat,
     },
-    /// launches the scp command for the selected index of the table or the specified connection mane in the table, use "con:"<path> to be replaced with the connection to the selected ssh server
+    /// copies files with scp, rsync or sftp for the selected index of the table or the specified connection name in the table, use "con:"<path> for the paths in the selected ssh server and "<Host>:"<path> for the paths in other servers of the table
     Copy {
         /// Index of the selected connection
-        #[clap(value_parser, value_name = "Selection")]
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
         selection: String,
 
-        /// Path to copy the file from, use "con:" at the beginning to indicate that this path is on the remote server.
-        #[clap(value_parser, value_name = "From")]
-        from: String,
+        /// Paths to copy the files from, use "con:" at the beginning to indicate that the path is on the remote server.
+        #[clap(value_parser, value_name = "From", required = true, num_args = 1..)]
+        from: Vec<String>,
 
-        /// Path to copy the file to, use "con:" at the beginning to indicate that this path is on the remote server.
+        /// Path to copy the files to, use "con:" at the beginning to indicate that this path is on the remote server.
         #[clap(value_parser, value_name = "To")]
         to: String,
 
+        /// Program used to copy the files
+        #[clap(long, value_enum, value_name = "backend", default_value_t = CopyBackend::Scp)]
+        via: CopyBackend,
+
+        /// Command to use instead of the program of the backend
+        #[clap(short, long, value_parser, value_name = "command")]
+        command: Option<String>,
+
+        /// Copies the directories recursively
+        #[clap(short, long, value_parser, default_value_t = false)]
+        recursive: bool,
+
+        /// Additional args to use in the command
+        #[clap(short, long, value_parser, value_name = "args")]
+        args: Option<Vec<String>>,
+    },
+
+    /// launches the sftp command for the selected index of the table or the specified connection name in the table
+    Sftp {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
+        selection: String,
+
+        /// Additional args to use in the command
+        #[clap(short, long, value_parser, value_name = "args")]
+        args: Option<Vec<String>>,
+
         /// Command to use
-        #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("scp"))]
+        #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("sftp"))]
         command: String,
     },
+    /// browses the directories of the selected index of the table or the specified connection name in the table, downloading and uploading files, sftp runs in batch mode so the host has to log in with a key or an agent without asking for a password
+    Browse {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
+        selection: String,
+
+        /// Additional args to use in the sftp command
+        #[clap(short, long, value_parser, value_name = "args")]
+        args: Option<Vec<String>>,
+
+        /// Command to use
+        #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("sftp"))]
+        command: String,
+    },
+    /// mounts a directory of the selected index of the table or the specified connection name in the table with sshfs
+    Mount {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates), required_unless_present = "list")]
+        selection: Option<String>,
+
+        /// Remote directory to mount, the home directory by default
+        #[clap(value_parser, value_name = "RemotePath")]
+        remote_path: Option<String>,
+
+        /// Local directory to mount it on, "<mount dir>/<Host>" by default
+        #[clap(value_parser, value_name = "Mountpoint")]
+        mountpoint: Option<PathBuf>,
+
+        /// Lists the directories mounted by ssh-view
+        #[clap(short, long, value_parser, default_value_t = false, conflicts_with = "selection")]
+        list: bool,
+
+        /// Directory where the default mountpoints are created, SSH_VIEW_MOUNT_DIR or "~/.ssh-view/mnt" by default
+        #[clap(long, value_parser, value_name = "DIR")]
+        mount_dir: Option<PathBuf>,
+
+        /// Command to use
+        #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("sshfs"))]
+        command: String,
+
+        /// Additional args to use in the command
+        #[clap(short, long, value_parser, value_name = "args")]
+        args: Option<Vec<String>>,
+    },
+    /// unmounts a directory mounted with "mount", given its mountpoint or the connection name to unmount all its directories
+    Umount {
+        /// Mountpoint or connection name
+        #[clap(value_parser, value_name = "Target", required_unless_present = "all")]
+        target: Option<String>,
+
+        /// Unmounts every directory mounted by ssh-view
+        #[clap(long, value_parser, default_value_t = false)]
+        all: bool,
+    },
 
     /// Adds a new entry to the ssh config file.
     Add {
@@ -140,17 +353,22 @@ enum Commands {
         )]
         identities_only: bool,
     },
+    /// adds hosts to the ssh config file from other sources
+    Import {
+        #[clap(subcommand)]
+        source: ImportCommand,
+    },
     /// Deletes an entry from the ssh config file
     Delete {
-        /// Index of the selected entry to delete
-        #[clap(value_parser, value_name = "Selection")]
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
         selection: String,
     },
     /// makes a ssh tunnel for the selected index of the table or the specified connection name in the table
     Tunnel {
         /// Index of the selected connection
-        #[clap(value_parser, value_name = "Selection")]
-        selection: String,
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(completion::selection_candidates))]
+        selection: Option<String>,
 
         /// Command to use
         #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("ssh"))]
@@ -158,55 +376,144 @@ enum Commands {
 
         /// tunnel mode
         #[clap(subcommand)]
-        mode: Option<TunnelMode>,
+        mode: Option<TunnelCommand>,
 
         /// Additional args to use in the command
         #[clap(short, long, value_parser, value_name = "args")]
         args: Option<Vec<String>>,
+
+        /// Saves the tunnel with this name in the ssh-view metadata file so it can be started with "tunnel up <Profile>"
+        #[clap(short, long, value_parser, value_name = "Profile")]
+        save: Option<String>,
+
+        /// Runs the tunnel in background, use "tunnel list" and "tunnel stop" to manage it
+        #[clap(short, long, value_parser, default_value_t = false, conflicts_with = "keep_alive")]
+        background: bool,
+
+        /// Restarts the tunnel when it exits or the local port stops answering
+        #[clap(short, long, value_parser, default_value_t = false)]
+        keep_alive: bool,
+
+        /// Seconds between the checks of the local port of the tunnel when using --keep-alive
+        #[clap(long, value_parser, value_name = "seconds", default_value_t = 30u64)]
+        probe_interval: u64,
+
+        /// Maximum seconds to wait before restarting the tunnel when using --keep-alive
+        #[clap(long, value_parser, value_name = "seconds", default_value_t = 300u64)]
+        max_backoff: u64,
+
+        /// Adds a local forward with the ssh -L syntax, "[bind_address:]port:host:hostport", "[bind_address:]port:remote_socket"...
+        #[clap(short('L'), long, value_parser = Forward::parse_local, value_name = "spec")]
+        local_forward: Vec<Forward>,
+
+        /// Adds a remote forward with the ssh -R syntax, "[bind_address:]port:host:hostport", "remote_socket:local_socket"...
+        #[clap(short('R'), long, value_parser = Forward::parse_remote, value_name = "spec")]
+        remote_forward: Vec<Forward>,
+
+        /// Adds a dynamic forward with the ssh -D syntax, "[bind_address:]port"
+        #[clap(short('D'), long, value_parser = Forward::parse_dynamic, value_name = "spec")]
+        dynamic_forward: Vec<Forward>,
+
+        /// Local port of the tunnel mode or of the saved tunnel started with "tunnel up", "auto" picks a free port
+        /// from the range
+        #[clap(long, value_parser, value_name = "LocalPort")]
+        local_port: Option<LocalPort>,
+
+        /// Range of local ports to pick from when a local port is "auto"
+        #[clap(long, value_parser, value_name = "start-end", default_value = "20000-29999")]
+        port_range: PortRange,
+
+        /// Format of the information printed about the tunnel, json also prints it when no port is "auto"
+        #[clap(short, long, value_enum, value_name = "format", default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
     },
-}
+    /// runs a command on every connection matching the filter in parallel, use "--" to separate the remote command
+    Exec {
+        /// Filter for the connections, a regex matching a whole connection name or "tag:<Tag>"
+        #[clap(value_parser, value_name = "Filter", add = ArgValueCandidates::new(completion::selection_candidates))]
+        filter: String,
 
-#[derive(Subcommand)]
-enum TunnelMode {
-    Local {
-        /// Local port to use as one of the sides of the tunnel
-        #[clap(value_parser, value_name = "LocalPort")]
-        local_port: u16,
-
-        /// Remote host to forward traffic from the tunnel
-        #[clap(value_parser, value_name = "RemoteHost", default_value_t = String::from("127.0.0.1"))]
-        remote_host: String,
-
-        /// Remote port to forward traffic from the tunnel
-        #[clap(value_parser, value_name = "RemotePort", default_value = "80")]
-        remote_port: u16,
+        /// Command to run on the remote hosts
+        #[clap(value_parser, value_name = "RemoteCommand", last = true, required = true)]
+        remote_command: Vec<String>,
+
+        /// Command to use
+        #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("ssh"))]
+        command: String,
+
+        /// Additional args to use in the command
+        #[clap(short, long, value_parser, value_name = "args")]
+        args: Option<Vec<String>>,
+
+        /// Maximum number of hosts to run the command on at the same time
+        #[clap(short, long, value_parser, value_name = "jobs", default_value_t = 10usize)]
+        jobs: usize,
+
+        /// Groups the output of every host instead of prefixing each line with the host name
+        #[clap(short, long, value_parser, default_value_t = false)]
+        group: bool,
+
+        /// Format of the summary
+        #[clap(short, long, value_enum, value_name = "format", default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
     },
-    Remote {
-        /// Local port to forward traffic from the tunnel
-        #[clap(value_parser, value_name = "LocalPort")]
-        local_port: u16,
-
-        /// Local host to forward traffic from the tunnel
-        #[clap(value_parser, value_name = "LocalHost", default_value_t = String::from("127.0.0.1"))]
-        local_host: String,
-
-        /// Remote port to forward traffic to the tunnel
-        #[clap(value_parser, value_name = "RemotePort", default_value = "80")]
-        remote_port: u16,
+    /// opens an interactive session to every connection matching the filter and sends every typed line to all of them
+    Broadcast {
+        /// Filter for the connections, a regex matching a whole connection name or "tag:<Tag>"
+        #[clap(value_parser, value_name = "Filter", add = ArgValueCandidates::new(completion::selection_candidates))]
+        filter: String,
+
+        /// Command to use
+        #[clap(short, long, value_parser, value_name = "command", default_value_t = String::from("ssh"))]
+        command: String,
+
+        /// Additional args to use in the command
+        #[clap(short, long, value_parser, value_name = "args")]
+        args: Option<Vec<String>>,
+    },
+    /// checks if the connections are reachable connecting to their HostName and Port and reading the ssh server version
+    Check {
+        /// Optional filter for the connections, "tag:<Tag>" shows the connections with the tag
+        #[clap(add = ArgValueCandidates::new(completion::selection_candidates))]
+        filter: Option<String>,
+
+        /// Checks the first host of the ProxyJump chain instead of the HostName when the connection uses ProxyJump
+        #[clap(short, long, value_parser, default_value_t = false)]
+        proxy_jump: bool,
+
+        /// Seconds to wait for the connection and the server version
+        #[clap(short, long, value_parser, value_name = "timeout", default_value_t = 5u64)]
+        timeout: u64,
+
+        /// Maximum number of hosts to check at the same time
+        #[clap(short, long, value_parser, value_name = "jobs", default_value_t = 32usize)]
+        jobs: usize,
+
+        /// Format of the report
+        #[clap(short, long, value_enum, value_name = "format", default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
     },
-    Dynamic {
-        /// Local port to use as one of the sides of the tunnel
-        #[clap(value_parser, value_name = "LocalPort")]
-        local_port: u16,
+    /// writes the script that adds the completions to the shell, the connection names, tags and tunnel profiles are completed from the config file, e.g. "source <(ssh-view completions bash)"
+    Completions {
+        /// Shell to write the script for
+        #[clap(value_enum, value_name = "Shell")]
+        shell: CompletionShell,
     },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    CompleteEnv::with_factory(Cli::command).var(completion::COMPLETE_VAR).complete();
     let cli = Cli::parse();
+    // the script doesn't need the config file, the shell can load it before there is one
+    if let Some(Commands::Completions { shell }) = cli.command {
+        return completion::write_registration(shell, &mut io::stdout());
+    }
 
     #[allow(deprecated)]
         let mut buf = env::home_dir().unwrap();
     buf.push(Path::new(".ssh/config"));
+    // mosh only passes the config file to ssh when it isn't the default one, so ssh still reads the system config
+    let explicit_config = cli.config.is_some();
     let config_file = match cli.config {
         Some(path) => path,
         None => buf,
@@ -215,42 +522,55 @@ fn main() -> Result<(), Box<dyn Error>> {
     let config_file = config_file.as_path();
     let config_file = config_file.canonicalize().unwrap();
     let config_file = config_file.as_path();
+    let launch = LaunchMode::new(cli.dry_run, cli.print);
 
     if cli.command.is_some() {
-        let data: Result<Vec<Vec<String>>, Box<dyn Error>> = read_ssh_config_file(config_file);
+        let data: Result<Vec<Vec<String>>, Box<dyn Error>> =
+            read_ssh_config_file(config_file).map(|data| {
+                let metadata = Metadata::load(config_file).unwrap_or_else(|e| {
+                    eprintln!("couldn't read the pinned hosts: {e}");
+                    Metadata::default()
+                });
+                add_pins(data, &metadata)
+            });
         return match &cli.command {
             Some(Commands::Show {
-                     filter
+                     filter,
+                     details,
+                     sort,
                  }) => {
                 if !(config_file.exists() && config_file.is_file()) {
                     panic!("couldnt open {:#?}", config_file.as_os_str())
                 }
-                let filter = filter.clone().map(|filter_str| format!(".*{filter_str}.*")).unwrap_or(String::from(r".*"));
-                let filter = Regex::new(filter.as_str()).unwrap();
-                data.map(|data| {
-                    let mut header = true;
-                    data.iter().filter(|row| {
-                        let old_header = header;
-                        if header {
-                            header = false;
-                        }
-                        row.iter().any(|cell| old_header || filter.is_match(cell))
-                    })
-                        .map(|row| row.to_owned())
-                        .collect::<Vec<Vec<String>>>()
-                })
+                data.and_then(|data| filter_rows(data, filter))
+                    .and_then(|data| sort_rows(data, sort, config_file))
                     .map(|data| {
                         let mut table = Table::new();
                         let mut header = true;
+                        // the pin column goes first when there are pinned hosts, the description and the notes last
+                        let mut columns = vec![];
+                        if data.iter().skip(1).any(|row| !row[7].is_empty()) {
+                            columns.push(7);
+                        }
+                        columns.extend(0..5);
+                        if *details {
+                            columns.extend([5, 6]);
+                        }
                         for row in data {
+                            let pinned = !header && !row[7].is_empty();
                             table.add_row(Row::new(
-                                row.iter()
-                                    .map(|cell| {
-                                        let table_cell = Cell::new(cell.as_str());
+                                columns
+                                    .iter()
+                                    .map(|column| {
+                                        let table_cell = Cell::new(row[*column].as_str());
                                         if header {
                                             table_cell
                                                 .with_style(Attr::Bold)
                                                 .with_style(Attr::ForegroundColor(color::GREEN))
+                                        } else if pinned {
+                                            table_cell
+                                                .with_style(Attr::Bold)
+                                                .with_style(Attr::ForegroundColor(color::YELLOW))
                                         } else {
                                             table_cell.with_style(Attr::ForegroundColor(color::CYAN))
                                         }
@@ -269,55 +589,253 @@ fn main() -> Result<(), Box<dyn Error>> {
                      selection,
                      args,
                      command,
-                 }) => data.map(|data| {
+                 }) => data.and_then(|data| {
                 let connection_name = get_connection_name(data, selection);
-                let mut command = Command::new(command);
-                let command = command
-                    .arg(connection_name)
-                    .stdin(Stdio::inherit())
-                    .stdout(Stdio::inherit())
-                    .stderr(Stdio::inherit());
-                match args {
-                    None => {}
-                    Some(args) => {
-                        command.args(args.iter());
+                connect(config_file, explicit_config, connection_name.as_str(), command, args, &launch)
+            }),
+            Some(Commands::Recent {
+                     number,
+                     limit,
+                     args,
+                     command,
+                 }) => data.and_then(|data| {
+                let recent = history::recent_hosts(config_file)?;
+                match number {
+                    None => {
+                        history::print_recent(&recent[..recent.len().min(*limit)]);
+                        Ok(())
                     }
-                };
-                command.spawn().unwrap().wait().unwrap();
+                    Some(number) => {
+                        let host = recent.get(*number).ok_or_else(|| {
+                            format!("there is no connection with the number {number} in the history")
+                        })?;
+                        let connection_name = get_connection_name(data, &host.last.host);
+                        connect(config_file, explicit_config, connection_name.as_str(), command, args, &launch)
+                    }
+                }
+            }),
+            Some(Commands::Tag { action }) => data.and_then(|data| match action {
+                TagCommand::List => {
+                    tags::print_tags(&data);
+                    Ok(())
+                }
+                TagCommand::Add { selection, tags } | TagCommand::Remove { selection, tags } => {
+                    let connection_name = get_connection_name(data.clone(), selection);
+                    let index = get_connection_index(data, selection);
+                    if !launch.spawns() {
+                        match action {
+                            TagCommand::Add { .. } => eprintln!("would add the tags {} to {}", tags.join(","), connection_name),
+                            _ => eprintln!("would remove the tags {} from {}", tags.join(","), connection_name),
+                        }
+                        return Ok(());
+                    }
+                    let (file, index) = ssh_config::block_file(config_file, index)?;
+                    match action {
+                        TagCommand::Add { .. } => tags::update_tags(&file, index, tags, &[]),
+                        _ => tags::update_tags(&file, index, &[], tags),
+                    }
+                }
+            }),
+            Some(Commands::Note { selection }) => data.and_then(|data| {
+                let connection_name = get_connection_name(data.clone(), selection);
+                let (file, index) = ssh_config::block_file(config_file, get_connection_index(data, selection))?;
+                notes::edit_notes(&file, index, connection_name.as_str(), &launch)
             }),
+            Some(Commands::Pin { selection }) => data.and_then(|data| {
+                let connection_name = get_connection_name(data, selection);
+                let mut metadata = Metadata::load(config_file)?;
+                let pin = metadata.pin_host(connection_name.as_str());
+                if !launch.spawns() {
+                    eprintln!("would pin {connection_name} as @{pin}");
+                    return Ok(());
+                }
+                metadata.save(config_file)?;
+                println!("{connection_name} is pinned as @{pin}");
+                Ok(())
+            }),
+            Some(Commands::Unpin { selection }) => data.and_then(|data| {
+                let connection_name = get_connection_name(data, selection);
+                let mut metadata = Metadata::load(config_file)?;
+                metadata.update_host(connection_name.as_str(), |host| host.pin = None);
+                if launch.spawns() {
+                    metadata.save(config_file)?;
+                }
+                Ok(())
+            }),
+            Some(Commands::Launcher {
+                     selection,
+                     launcher,
+                 }) => data.and_then(|data| {
+                let connection_name = get_connection_name(data, selection);
+                let mut metadata = Metadata::load(config_file)?;
+                match launcher {
+                    None => println!("{} uses {}", connection_name, metadata.launcher(connection_name.as_str()).program()),
+                    Some(launcher) if !launch.spawns() => {
+                        eprintln!("would set the launcher of {} to {}", connection_name, launcher.program())
+                    }
+                    Some(launcher) => {
+                        metadata.update_host(connection_name.as_str(), |host| {
+                            host.launcher = Some(*launcher).filter(|launcher| *launcher != Launcher::Ssh)
+                        });
+                        metadata.save(config_file)?;
+                    }
+                }
+                Ok(())
+            }),
+            Some(Commands::Export { list: Some(list), .. }) => {
+                let filter = match list {
+                    ExportListCommand::Csv { filter } | ExportListCommand::Json { filter } => filter,
+                };
+                data.and_then(|data| filter_rows(data, filter)).and_then(|data| {
+                    let ssh_hosts = ssh_config::read_ssh_hosts(config_file)?;
+                    let records = data
+                        .iter()
+                        .skip(1)
+                        .filter_map(|row| row[0].parse::<usize>().ok())
+                        .filter_map(|index| ssh_hosts.iter().find(|host| host.index == index))
+                        .map(host_list::host_record)
+                        .collect::<Vec<host_list::HostRecord>>();
+                    match list {
+                        ExportListCommand::Csv { .. } => print!("{}", host_list::write_csv(&records)?),
+                        ExportListCommand::Json { .. } => println!("{}", host_list::write_json(&records)?),
+                    }
+                    Ok(())
+                })
+            }
             Some(Commands::Export {
+                     list: None,
                      selection,
                      args,
                      command,
-                 }) => data.map(|data| {
-                let connection_name = get_connection_name(data, selection);
-                let mut clipboard = Clipboard::new().unwrap();
-                let args_str = match args {
-                    Some(args) => args.join(" "),
-                    None => String::new(),
+                     shell,
+                     export_as,
+                     target,
+                 }) => data.and_then(|data| {
+                let connection_name = get_connection_name(data, &selection.clone().unwrap_or_default());
+                let hosts = ssh_config::read_ssh_hosts(config_file)?;
+                // the options are resolved for the first alias of the Host line, as ssh would for that name
+                let alias = connection_name.split_whitespace().next().unwrap_or_default();
+                let text = match export_as {
+                    ExportAs::Command => shell.command_line(&use_command(command, alias, args)),
+                    ExportAs::CommandExpanded => {
+                        shell.command_line(&share::expanded_command(command, &hosts, alias, args))
+                    }
+                    ExportAs::Config => share::host_config(&hosts, alias),
+                    ExportAs::Uri => share::uri(&hosts, alias),
                 };
-                clipboard
-                    .set_text(format!("{} {} {}", command, connection_name, args_str))
-                    .unwrap();
+                match launch {
+                    LaunchMode::Spawn => {
+                        clipboard::export_text(text.as_str(), *target)?;
+                    }
+                    LaunchMode::DryRun => eprintln!("would export to the {target}: {text}"),
+                    LaunchMode::Print => println!("{text}"),
+                }
+                Ok(())
             }),
+            Some(Commands::ExportInventory { filter, format }) => data
+                .and_then(|data| filter_rows(data, filter))
+                .and_then(|data| {
+                    let ssh_hosts = ssh_config::read_ssh_hosts(config_file)?;
+                    let hosts = data
+                        .iter()
+                        .skip(1)
+                        .filter(|row| !ssh_config::is_pattern(row[1].as_str()))
+                        .map(|row| {
+                            let alias = row[1].split_whitespace().next().unwrap_or_default();
+                            ansible::inventory_host(alias, row[4].as_str(), &ssh_hosts)
+                        })
+                        .collect::<Vec<ansible::InventoryHost>>();
+                    match format {
+                        InventoryFormat::Ansible => print!("{}", ansible::inventory_ini(&hosts)),
+                        InventoryFormat::AnsibleYaml => print!("{}", ansible::inventory_yaml(&hosts)?),
+                    }
+                    Ok(())
+                }),
             Some(Commands::Copy {
                      selection,
                      from,
                      to,
+                     via,
                      command,
-                 }) => data.map(|data| {
+                     recursive,
+                     args,
+                 }) => data.and_then(|data| {
+                // every name of a Host line can be used as "<Host>:", the connection is copied with its first one
+                let host_lines: Vec<String> = data
+                    .iter()
+                    .skip(1)
+                    .filter(|row| !ssh_config::is_pattern(row[1].as_str()))
+                    .map(|row| row[1].clone())
+                    .collect();
+                let aliases: Vec<String> =
+                    host_lines.iter().flat_map(|line| line.split_whitespace().map(String::from)).collect();
                 let connection_name = get_connection_name(data, selection);
-                let mut command = Command::new(command);
-                let from = from.replace("con:", format!("{}:", connection_name).as_str());
-                let to = to.replace("con:", format!("{}:", connection_name).as_str());
-                let command = command
-                    .arg(from)
-                    .arg(to)
-                    .stdin(Stdio::inherit())
-                    .stdout(Stdio::inherit())
-                    .stderr(Stdio::inherit());
-                command.spawn().unwrap().wait().unwrap();
+                let alias = connection_name.split_whitespace().next().unwrap_or_default();
+                let sources: Vec<Location> = from.iter().map(|path| Location::parse(path, alias, &aliases)).collect();
+                let destination = Location::parse(to, alias, &aliases);
+                let options = CopyOptions {
+                    via: *via,
+                    command,
+                    recursive: *recursive,
+                    args,
+                };
+                let started = Instant::now();
+                let status = copy::run_copy(copy::copy_command(&sources, &destination, &options)?, &launch)?;
+                if let Some(status) = status {
+                    // the history has the hosts the files were copied from and to, the selected one may not be used
+                    for host in copy::remote_hosts(&sources, &destination) {
+                        let host_line = host_lines
+                            .iter()
+                            .find(|line| line.split_whitespace().any(|name| name == host))
+                            .map_or(host, |line| line.as_str());
+                        history::record(config_file, host_line, Activity::Copy, started, status.code(), &launch);
+                    }
+                }
+                Ok(())
             }),
+            Some(Commands::Sftp {
+                     selection,
+                     args,
+                     command,
+                 }) => data.and_then(|data| {
+                let connection_name = get_connection_name(data, selection);
+                launch.run(&mut browse::sftp_command(command, connection_name.as_str(), args))?;
+                Ok(())
+            }),
+            Some(Commands::Browse {
+                     selection,
+                     args,
+                     command,
+                 }) => data.and_then(|data| {
+                let connection_name = get_connection_name(data, selection);
+                if !launch.announce(&browse::sftp_command(command, connection_name.as_str(), args)) {
+                    return Ok(());
+                }
+                browse::browse(command, connection_name.as_str(), args)
+            }),
+            Some(Commands::Mount {
+                     selection,
+                     remote_path,
+                     mountpoint,
+                     list,
+                     mount_dir,
+                     command,
+                     args,
+                 }) => match selection {
+                None if *list => mount::print_mounts(),
+                None => Err(Box::new(CommandError::new("Mount", "no connection selected")) as Box<dyn Error>),
+                Some(selection) => data.and_then(|data| {
+                    let connection_name = get_connection_name(data, selection);
+                    let options = MountOptions {
+                        command,
+                        args,
+                        mount_dir,
+                        launch,
+                    };
+                    mount::mount(connection_name.as_str(), remote_path, mountpoint, &options)
+                }),
+            },
+            Some(Commands::Umount { target, all }) => mount::unmount(target, *all, &launch),
             Some(Commands::Add {
                      host,
                      host_name,
@@ -329,8 +847,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                 if !(config_file.exists() && config_file.is_file()) {
                     panic!("couldnt find {:#?}", config_file.as_os_str())
                 }
+                if !launch.spawns() {
+                    let mut block = vec![];
+                    add_entry(&mut block, host, host_name, user, port, identity_file, identities_only)?;
+                    eprintln!("would add:{}", String::from_utf8_lossy(&block).trim_end());
+                    return Ok(());
+                }
                 let host_file = OpenOptions::new()
-                    .write(true)
                     .append(true)
                     .open(config_file);
                 match host_file {
@@ -346,48 +869,48 @@ fn main() -> Result<(), Box<dyn Error>> {
                     Err(e) => Err(Box::new(e)),
                 }
             }
-            Some(Commands::Delete { selection }) => selection
-                .trim()
-                .parse::<usize>()
-                .map(|selected_index| selected_index as i32)
-                .map(|mut selected_index| {
+            Some(Commands::Import { source }) => match source {
+                ImportCommand::KnownHosts { file, all, user } => {
+                    #[allow(deprecated)]
+                    let file = file.clone().unwrap_or(env::home_dir().unwrap().join(".ssh/known_hosts"));
+                    import::import_known_hosts(config_file, &file, user, *all, &launch)
+                }
+                ImportCommand::Ansible { file, yes } => import::import_ansible(config_file, file, *yes, &launch),
+                ImportCommand::Putty { file, yes } => import::import_putty(config_file, file, *yes, &launch),
+                ImportCommand::Csv { file, mapping, yes } => {
+                    import::import_host_list(config_file, file, HostListFormat::Csv, mapping, *yes, &launch)
+                }
+                ImportCommand::Json { file, mapping, yes } => {
+                    import::import_host_list(config_file, file, HostListFormat::Json, mapping, *yes, &launch)
+                }
+            },
+            Some(Commands::Delete { selection }) => data
+                .map(|data| get_connection_index(data, selection))
+                .and_then(|index| ssh_config::block_file(config_file, index))
+                .map(|(config_file, selected_index)| {
                     let read_file =
                         OpenOptions::new()
                             .read(true)
-                            .open(config_file)
+                            .open(&config_file)
                             .map(|host_file| {
-                                let lines = BufReader::new(host_file).lines();
-                                let mut copy = true;
-                                let mut new_host_contents = String::new();
-                                use std::fmt::Write;
-                                let mut selected_host = None;
-                                for line in lines.flatten() {
-                                    if line.starts_with("Host ") {
-                                        if selected_index != 0 {
-                                            copy = true;
-                                            writeln!(new_host_contents).expect("");
-                                        } else {
-                                            selected_host = Some(
-                                                line.replace("Host ", "").trim().to_string(),
-                                            );
-                                            copy = false;
-                                        }
-                                        selected_index -= 1;
+                                let lines = BufReader::new(host_file).lines().map_while(Result::ok);
+                                let (new_host_contents, selected_host) = remove_host_block(lines, selected_index);
+                                let mut confirmation = false;
+                                match selected_host {
+                                    Some(selected_host) if !launch.spawns() => {
+                                        eprintln!("would delete the host \"{selected_host}\"")
                                     }
-                                    if copy {
-                                        writeln!(new_host_contents, "{line}").expect("");
+                                    Some(selected_host) => {
+                                        println!(
+                                            "The host \"{selected_host}\" will be deleted, are you sure?"
+                                        );
+                                        println!("Type \"yes\" to confirm");
+                                        let stdin = io::stdin();
+                                        let mut response = String::new();
+                                        stdin.read_line(&mut response).unwrap();
+                                        confirmation = response.trim() == "yes";
                                     }
-                                }
-                                let mut confirmation = false;
-                                if let Some(selected_host) = selected_host {
-                                    println!(
-                                        "The host \"{selected_host}\" will be deleted, are you sure?"
-                                    );
-                                    println!("Type \"yes\" to confirm");
-                                    let stdin = io::stdin();
-                                    let mut response = String::new();
-                                    stdin.read_line(&mut response).unwrap();
-                                    confirmation = response.trim() == "yes";
+                                    None => {}
                                 }
                                 (new_host_contents, confirmation)
                             });
@@ -395,7 +918,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                         if confirmation {
                             OpenOptions::new()
                                 .write(true)
-                                .open(config_file)
+                                .open(&config_file)
                                 .map(|host_file| {
                                     host_file.set_len(0).unwrap();
                                     BufWriter::new(host_file)
@@ -405,63 +928,169 @@ fn main() -> Result<(), Box<dyn Error>> {
                                 .unwrap();
                         }
                     }
-                })
-                .map_err(|e| Box::new(e) as Box<dyn Error>),
+                }),
             Some(Commands::Tunnel {
                      selection,
                      command,
                      mode,
-                     args
+                     args,
+                     save,
+                     background,
+                     keep_alive,
+                     probe_interval,
+                     max_backoff,
+                     local_forward,
+                     remote_forward,
+                     dynamic_forward,
+                     local_port,
+                     port_range,
+                     format,
                  }) => {
-                match mode {
-                    None => {
-                        return Err(Box::new(TunnelModeError::new("no tunnel mode selected")));
+                let extra_forwards = local_forward
+                    .iter()
+                    .chain(remote_forward.iter())
+                    .chain(dynamic_forward.iter())
+                    .cloned()
+                    .collect::<Vec<Forward>>();
+                let supervision = if *background {
+                    Supervision::Background
+                } else if *keep_alive {
+                    Supervision::KeepAlive {
+                        probe_interval: Duration::from_secs(*probe_interval),
+                        max_backoff: Duration::from_secs(*max_backoff),
                     }
-                    Some(tunnel_mode) => {
-                        data.map(|data| {
+                } else {
+                    Supervision::Foreground
+                };
+                let options = TunnelOptions {
+                    supervision,
+                    port_range: *port_range,
+                    format: *format,
+                    launch,
+                };
+                match mode {
+                    None | Some(TunnelCommand::Mode(_)) => match selection {
+                        None => Err(Box::new(CommandError::new("Tunnel Mode", "no connection selected")) as Box<dyn Error>),
+                        Some(selection) => data.and_then(|data| {
+                            let mut forwards = vec![];
+                            if let Some(TunnelCommand::Mode(tunnel_mode)) = mode {
+                                forwards.push(Forward::from(tunnel_mode));
+                            }
+                            if let Some(local_port) = local_port {
+                                set_local_port(&mut forwards, *local_port)?;
+                            }
+                            forwards.extend(extra_forwards);
+                            validate_forwards(&forwards)?;
                             let connection_name = get_connection_name(data, selection);
-                            let mut command = Command::new(command);
-                            match tunnel_mode {
-                                TunnelMode::Local {
-                                    local_port,
-                                    remote_host,
-                                    remote_port
-                                } => {
-                                    command.arg("-L")
-                                        .arg(format!("{}:{}:{}",local_port, remote_host,remote_port))
-                                }
-                                TunnelMode::Remote {
-                                    local_port,
-                                    local_host ,
-                                    remote_port
-                                } => {
-                                    command.arg("-R")
-                                        .arg(format!("{}:{}:{}",remote_port, local_host,local_port))
-                                }
-                                TunnelMode::Dynamic {
-                                    local_port
-                                } => {
-                                    command.arg("-D")
-                                        .arg(local_port.to_string())
-                                }
-                            };
-                            let command = command
-                                .arg(connection_name)
-                                .stdin(Stdio::inherit())
-                                .stdout(Stdio::inherit())
-                                .stderr(Stdio::inherit());
-                            match args {
-                                None => {}
-                                Some(args) => {
-                                    command.args(args.iter());
-                                }
-                            };
-                            command.spawn().unwrap().wait().unwrap();
-                        })
-                    }
-                }.expect("Error creating tunnel");
-                return Ok(());
+                            if let (Some(profile), true) = (save, launch.spawns()) {
+                                let mut metadata = Metadata::load(config_file)?;
+                                metadata.tunnels.insert(profile.clone(), TunnelProfile {
+                                    host: tunnel::first_alias(connection_name.as_str()).to_string(),
+                                    forwards: forwards.clone(),
+                                    args: args.clone(),
+                                });
+                                metadata.save(config_file)?;
+                            }
+                            let started = Instant::now();
+                            let status = tunnel::start_tunnel(
+                                command,
+                                connection_name.as_str(),
+                                &forwards,
+                                args,
+                                &options,
+                            )?;
+                            let exit_code = status.and_then(|status| status.code());
+                            history::record(config_file, connection_name.as_str(), Activity::Tunnel, started, exit_code, &launch);
+                            Ok(())
+                        }),
+                    },
+                    Some(TunnelCommand::Up { profile }) => data.and_then(|data| {
+                        let metadata = Metadata::load(config_file)?;
+                        let profile = tunnel::find_profile(&metadata, profile)?;
+                        let mut forwards = profile.forwards.clone();
+                        if let Some(local_port) = local_port {
+                            set_local_port(&mut forwards, *local_port)?;
+                        }
+                        forwards.extend(extra_forwards);
+                        validate_forwards(&forwards)?;
+                        let connection_name = get_connection_name(data, &profile.host);
+                        let args = args.clone().or(profile.args.clone());
+                        let started = Instant::now();
+                        let status = tunnel::start_tunnel(
+                            command,
+                            connection_name.as_str(),
+                            &forwards,
+                            &args,
+                            &options,
+                        )?;
+                        let exit_code = status.and_then(|status| status.code());
+                        history::record(config_file, connection_name.as_str(), Activity::Tunnel, started, exit_code, &launch);
+                        Ok(())
+                    }),
+                    Some(TunnelCommand::Profiles) => Metadata::load(config_file)
+                        .map(|metadata| tunnel::print_profiles(&metadata)),
+                    Some(TunnelCommand::Forget { profile }) => Metadata::load(config_file)
+                        .and_then(|mut metadata| {
+                            tunnel::find_profile(&metadata, profile)?;
+                            if !launch.spawns() {
+                                eprintln!("would forget the tunnel profile {profile}");
+                                return Ok(());
+                            }
+                            metadata.tunnels.remove(profile);
+                            metadata.save(config_file)
+                        }),
+                    Some(TunnelCommand::List) => tunnel::print_running_tunnels(),
+                    Some(TunnelCommand::Stop { id, all }) => tunnel::stop_tunnels(id, *all, &launch),
+                }
             }
+            Some(Commands::Exec {
+                     filter,
+                     remote_command,
+                     command,
+                     args,
+                     jobs,
+                     group,
+                     format,
+                 }) => data
+                .and_then(|data| target_hosts(&data, filter))
+                .and_then(|hosts| {
+                    if !launch.spawns() {
+                        for host in hosts {
+                            launch.announce(&exec::exec_command(host.as_str(), command, args, remote_command));
+                        }
+                        return Ok(());
+                    }
+                    exec::exec_on_hosts(hosts, command, args, remote_command, *jobs, *group, format)
+                }),
+            Some(Commands::Broadcast {
+                     filter,
+                     command,
+                     args,
+                 }) => data
+                .and_then(|data| target_hosts(&data, filter))
+                .and_then(|hosts| {
+                    if !launch.spawns() {
+                        for host in hosts {
+                            launch.announce(&exec::exec_command(host.as_str(), command, args, &[]));
+                        }
+                        return Ok(());
+                    }
+                    broadcast::broadcast_to_hosts(hosts, command, args)
+                }),
+            Some(Commands::Check {
+                     filter,
+                     proxy_jump,
+                     timeout,
+                     jobs,
+                     format,
+                 }) => data
+                .and_then(|data| filter_rows(data, filter))
+                .and_then(|data| {
+                    let ssh_hosts = ssh_config::read_ssh_hosts(config_file)?;
+                    let rows = data.into_iter().skip(1).collect();
+                    check::check_hosts(rows, &ssh_hosts, *proxy_jump, Duration::from_secs(*timeout), *jobs, format)
+                }),
+            Some(Commands::Completions { .. }) => Ok(()),
             None => {
                 return Ok(());
             }
@@ -471,7 +1100,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 }
 
 fn add_entry(
-    host_file: &mut File,
+    host_file: &mut impl Write,
     host: &String,
     host_name: &String,
     user: &String,
@@ -496,6 +1125,84 @@ fn add_entry(
     Ok(())
 }
 
+/// connects with the command or the launcher of the host and records the connection in the history
+fn connect(
+    config_file: &Path,
+    explicit_config: bool,
+    connection_name: &str,
+    command: &Option<String>,
+    args: &Option<Vec<String>>,
+    launch: &LaunchMode,
+) -> Result<(), Box<dyn Error>> {
+    let mut command = match command {
+        // the Host line may have several names, the command connects to the first one as the launchers do
+        Some(command) => use_command(command, connection_name.split_whitespace().next().unwrap_or_default(), args),
+        None => Metadata::load(config_file)?.launcher(connection_name).command(
+            connection_name,
+            args,
+            explicit_config.then_some(config_file),
+            &ssh_config::read_ssh_hosts(config_file)?,
+        ),
+    };
+    let started = Instant::now();
+    if let Some(status) = launch.run(&mut command)? {
+        history::record(config_file, connection_name, Activity::Use, started, status.code(), launch);
+    }
+    Ok(())
+}
+
+/// sorts the rows of the table keeping the header first
+fn sort_rows(mut data: Vec<Vec<String>>, sort: &SortOrder, config_file: &Path) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let rows = &mut data[1..];
+    match sort {
+        SortOrder::Index => {}
+        SortOrder::Name => rows.sort_by(|a, b| a[1].cmp(&b[1])),
+        SortOrder::Frecency => {
+            let scores = history::frecency(config_file)?;
+            rows.sort_by_key(|row| std::cmp::Reverse(scores.get(&row[1]).copied().unwrap_or_default()));
+        }
+    }
+    // the pinned hosts always go first, in the order of their shortcuts
+    rows.sort_by_key(|row| row[7].trim_start_matches('@').parse::<u32>().unwrap_or(u32::MAX));
+    Ok(data)
+}
+
+/// adds the shortcuts of the pinned hosts as the last column of the table
+fn add_pins(mut data: Vec<Vec<String>>, metadata: &Metadata) -> Vec<Vec<String>> {
+    for (row, data_row) in data.iter_mut().enumerate() {
+        let pin = match row {
+            0 => String::from("Pin"),
+            _ => metadata.pin(data_row[1].as_str()).map(|pin| format!("@{pin}")).unwrap_or_default(),
+        };
+        data_row.push(pin);
+    }
+    data
+}
+
+/// builds the command to connect to the selected connection
+fn use_command(command: &str, connection_name: &str, args: &Option<Vec<String>>) -> Command {
+    let mut command = Command::new(command);
+    command
+        .arg(connection_name)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command
+}
+
+/// returns the index in the table of the selected connection, see ssh_config::block_file for the file it is in
+fn get_connection_index(data: Vec<Vec<String>>, selection: &String) -> usize {
+    let connection_name = get_connection_name(data.clone(), selection);
+    data.iter()
+        .skip(1)
+        .find(|row| row[1] == connection_name)
+        .and_then(|row| row[0].parse::<usize>().ok())
+        .unwrap()
+}
+
 fn get_connection_name(data: Vec<Vec<String>>, index: &String) -> String {
     match index.trim().parse::<usize>() {
         Ok(index) => {
@@ -508,11 +1215,119 @@ fn get_connection_name(data: Vec<Vec<String>>, index: &String) -> String {
             }
             String::from(&data[index + 1][1])
         }
-        Err(_) => {
-            if data.iter().filter(|&row| row[1].eq(index)).count() == 0 {
-                panic!("no connection in the list with the name {}", index);
+        Err(_) if index.starts_with('@') => match data.iter().skip(1).find(|row| row[7].eq(index)) {
+            Some(row) => String::from(&row[1]),
+            None => panic!("no pinned connection with the shortcut {}", index),
+        },
+        Err(_) if index.starts_with("tag:") => {
+            let rows = data.iter().skip(1).filter(|row| tags::has_tags(row, index)).collect::<Vec<&Vec<String>>>();
+            if rows.len() != 1 {
+                panic!("{} connections in the list with the tags {}, only one can be selected", rows.len(), index);
+            }
+            String::from(&rows[0][1])
+        }
+        // any of the names of the Host line selects it
+        Err(_) => match data.iter().skip(1).find(|row| row[1].eq(index) || row[1].split_whitespace().any(|name| name == index)) {
+            Some(row) => String::from(&row[1]),
+            None => panic!("no connection in the list with the name {}", index),
+        },
+    }
+}
+
+fn filter_rows(
+    data: Vec<Vec<String>>,
+    filter: &Option<String>,
+) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    if let Some(filter) = filter.as_ref().filter(|filter| filter.starts_with("tag:")) {
+        return Ok(data
+            .into_iter()
+            .enumerate()
+            .filter(|(row, data_row)| *row == 0 || tags::has_tags(data_row, filter))
+            .map(|(_, data_row)| data_row)
+            .collect());
+    }
+    let filter = filter.clone().map(|filter_str| format!(".*{filter_str}.*")).unwrap_or(String::from(r".*"));
+    let filter = Regex::new(filter.as_str())?;
+    let mut header = true;
+    Ok(data.iter().filter(|row| {
+        let old_header = header;
+        if header {
+            header = false;
+        }
+        row.iter().any(|cell| old_header || filter.is_match(cell))
+    })
+        .map(|row| row.to_owned())
+        .collect::<Vec<Vec<String>>>())
+}
+
+/// the connections a command runs on, the filter has to match a whole name of the connection unlike the one of show,
+/// the wildcard blocks aren't connections
+fn target_hosts(data: &[Vec<String>], filter: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let rows = data.iter().skip(1).filter(|row| !ssh_config::is_pattern(row[1].as_str()));
+    if filter.starts_with("tag:") {
+        return Ok(rows
+            .filter(|row| tags::has_tags(row, filter))
+            .map(|row| row[1].split_whitespace().next().unwrap_or_default().to_string())
+            .collect());
+    }
+    let filter = Regex::new(format!("^(?:{filter})$").as_str())?;
+    Ok(rows
+        .filter_map(|row| row[1].split_whitespace().find(|name| filter.is_match(name)))
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// the lines of the config file without the Host block at the index, with the Host line of the removed block
+fn remove_host_block(lines: impl Iterator<Item = String>, index: usize) -> (String, Option<String>) {
+    use std::fmt::Write;
+    let mut selected_index = index as i32;
+    let mut copy = true;
+    let mut new_host_contents = String::new();
+    let mut selected_host = None;
+    for line in lines {
+        if line.trim().starts_with("Host ") {
+            if selected_index != 0 {
+                copy = true;
+                writeln!(new_host_contents).expect("");
+            } else {
+                selected_host = Some(line.trim().replace("Host ", ""));
+                copy = false;
+            }
+            selected_index -= 1;
+        }
+        if copy {
+            writeln!(new_host_contents, "{line}").expect("");
+        }
+    }
+    (new_host_contents, selected_host)
+}
+
+/// adds a row to the table for every Host block of the config data
+fn read_config_rows(data: &str, data_matrix: &mut Vec<Vec<String>>) {
+    let first_row = data_matrix.len();
+    for line in data.lines() {
+        let line = String::from(line);
+        let row = data_matrix.len().checked_sub(1).filter(|row| *row >= first_row);
+        if line.trim().starts_with("Host ") {
+            let mut data_row = vec![String::new(); 7];
+            data_row[0] = format!("{}", data_matrix.len());
+            data_row[1] = line.trim().replace("Host ", "");
+            data_matrix.push(data_row);
+        } else if let Some(data_row) = row.and_then(|row| data_matrix.get_mut(row)) {
+            if line.trim().starts_with("HostName ") {
+                data_row[2] = line.trim().replace("HostName ", "");
+            } else if line.trim().starts_with("user ") {
+                data_row[3] = line.trim().replace("user ", "");
+            } else if let Some(("tags", tags)) = annotations::annotation(line.as_str()) {
+                data_row[4] = tags::join_tags(&[data_row[4].as_str(), tags]);
+            } else if let Some((notes::DESCRIPTION, description)) = annotations::annotation(line.as_str()) {
+                data_row[5] = description.to_string();
+            } else if let Some((notes::NOTE, note)) = annotations::annotation(line.as_str()) {
+                if !data_row[6].is_empty() {
+                    data_row[6].push('\n');
+                }
+                data_row[6].push_str(note);
             }
-            String::from(index)
         }
     }
 }
@@ -527,27 +1342,12 @@ fn read_ssh_config_file(path: &Path) -> Result<Vec<Vec<String>>, Box<dyn Error>>
             data
         })
         .map(|data| {
-            let mut row = 0;
-            let mut first = true;
             let mut data_matrix = vec![];
-            for line in data.lines() {
-                let line = String::from(line);
-                if line.trim().starts_with("Host ") {
-                    if first {
-                        first = false;
-                    } else {
-                        row += 1;
-                    }
-                    let mut data_row = vec![String::new(); 4];
-                    data_row[0] = format!("{}", row);
-                    data_row[1] = line.trim().replace("Host ", "");
-                    data_matrix.push(data_row);
-                } else if line.trim().starts_with("HostName ") {
-                    let data_row = data_matrix.get_mut(row).unwrap();
-                    data_row[2] = line.trim().replace("HostName ", "");
-                } else if line.trim().starts_with("user ") {
-                    let data_row = data_matrix.get_mut(row).unwrap();
-                    data_row[3] = line.trim().replace("user ", "");
+            read_config_rows(data.as_str(), &mut data_matrix);
+            // the hosts of the included files go after the ones of the config file, so the indexes of its blocks don't change
+            for file in ssh_config::included_files(path) {
+                if let Ok(data) = std::fs::read_to_string(file) {
+                    read_config_rows(data.as_str(), &mut data_matrix);
                 }
             }
             data_matrix
@@ -558,6 +1358,9 @@ fn read_ssh_config_file(path: &Path) -> Result<Vec<Vec<String>>, Box<dyn Error>>
                 String::from("HostName"),
                 String::from("Host"),
                 String::from("User"),
+                String::from("Tags"),
+                String::from("Description"),
+                String::from("Notes"),
             ]];
             data_matrix.iter().for_each(|row| {
                 let row = row.to_vec();
@@ -567,3 +1370,65 @@ fn read_ssh_config_file(path: &Path) -> Result<Vec<Vec<String>>, Box<dyn Error>>
         })
         .map_err(|e| Box::new(e) as Box<dyn Error>)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn use_command_adds_the_args_after_the_host() {
+        let command = use_command("ssh", "web", &Some(vec![String::from("-p"), String::from("2222")]));
+        assert_eq!(command.get_program(), "ssh");
+        assert_eq!(test_support::args(&command), ["web", "-p", "2222"]);
+        assert_eq!(use_command("mosh", "db", &None).get_args().count(), 1);
+    }
+
+    #[test]
+    fn select_a_connection_by_any_of_its_names() {
+        let row = |index: &str, name: &str| [index, name, "", "", "", "", "", ""].map(String::from).to_vec();
+        let data = vec![row("Index", "HostName"), row("0", "web web.prod"), row("1", "db")];
+        assert_eq!(get_connection_name(data.clone(), &String::from("web.prod")), "web web.prod");
+        assert_eq!(get_connection_name(data.clone(), &String::from("web web.prod")), "web web.prod");
+        assert_eq!(get_connection_name(data.clone(), &String::from("1")), "db");
+        assert_eq!(get_connection_index(data, &String::from("web")), 0);
+    }
+
+    #[test]
+    fn delete_an_indented_host_block() {
+        let config = "Host web\n    HostName 10.0.0.1\n\nMatch all\n  Host db\n    HostName 10.0.0.2\n  Host cache\n    HostName 10.0.0.3\n";
+        let (contents, deleted) = remove_host_block(config.lines().map(String::from), 1);
+        assert_eq!(deleted.as_deref(), Some("db"));
+        assert_eq!(contents, "\nHost web\n    HostName 10.0.0.1\n\nMatch all\n\n  Host cache\n    HostName 10.0.0.3\n");
+    }
+
+    #[test]
+    fn sort_rows_with_the_pinned_hosts_first() {
+        let row = |index: &str, name: &str, pin: &str| [index, name, "", "", "", "", "", pin].map(String::from).to_vec();
+        let data = vec![
+            row("Index", "HostName", "Pin"),
+            row("0", "web", ""),
+            row("1", "db", "@2"),
+            row("2", "app", ""),
+            row("3", "cache", "@1"),
+        ];
+        let names = |sort: SortOrder| {
+            sort_rows(data.clone(), &sort, Path::new("/nonexistent/config"))
+                .unwrap()
+                .iter()
+                .map(|row| row[1].clone())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(names(SortOrder::Index), ["HostName", "cache", "db", "web", "app"]);
+        assert_eq!(names(SortOrder::Name), ["HostName", "cache", "db", "app", "web"]);
+    }
+
+    #[test]
+    fn add_pins_as_the_last_column() {
+        let mut metadata = Metadata::default();
+        metadata.pin_host("db");
+        let data = vec![vec![String::from("Index"), String::from("HostName")], vec![String::from("0"), String::from("db")]];
+        let data = add_pins(data, &metadata);
+        assert_eq!(data[0][2], "Pin");
+        assert_eq!(data[1][2], "@1");
+    }
+}
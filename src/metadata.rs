@@ -0,0 +1,127 @@
+use crate::launch::Launcher;
+use crate::tunnel::TunnelProfile;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Data ssh-view keeps about the hosts that has no place in the ssh config file, stored next to it
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Saved tunnels by profile name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tunnels: BTreeMap<String, TunnelProfile>,
+    /// Settings of each host by connection name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, HostMetadata>,
+}
+
+/// Settings of a host
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostMetadata {
+    /// program "use" connects with, ssh when it isn't set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launcher: Option<Launcher>,
+    /// shortcut of the pinned host, it is selected with "@<Pin>"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<u32>,
+}
+
+impl HostMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.launcher.is_none() && self.pin.is_none()
+    }
+}
+
+impl Metadata {
+    /// loads the metadata of the config file, an empty metadata is returned if the file doesn't exist yet
+    pub fn load(config_file: &Path) -> Result<Metadata, Box<dyn Error>> {
+        let path = metadata_file(config_file);
+        if !path.exists() {
+            return Ok(Metadata::default());
+        }
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn launcher(&self, connection_name: &str) -> Launcher {
+        self.hosts
+            .get(connection_name)
+            .and_then(|host| host.launcher)
+            .unwrap_or_default()
+    }
+
+    pub fn pin(&self, connection_name: &str) -> Option<u32> {
+        self.hosts.get(connection_name).and_then(|host| host.pin)
+    }
+
+    /// pins the host with the lowest free shortcut, a pinned host keeps its shortcut
+    pub fn pin_host(&mut self, connection_name: &str) -> u32 {
+        if let Some(pin) = self.pin(connection_name) {
+            return pin;
+        }
+        let pin = (1..)
+            .find(|pin| !self.hosts.values().any(|host| host.pin == Some(*pin)))
+            .unwrap_or_default();
+        self.update_host(connection_name, |host| host.pin = Some(pin));
+        pin
+    }
+
+    /// changes the settings of the host, removing it when it has nothing set
+    pub fn update_host<F: FnOnce(&mut HostMetadata)>(&mut self, connection_name: &str, update: F) {
+        let host = self.hosts.entry(connection_name.to_string()).or_default();
+        update(host);
+        if host.is_empty() {
+            self.hosts.remove(connection_name);
+        }
+    }
+
+    pub fn save(&self, config_file: &Path) -> Result<(), Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(metadata_file(config_file))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+/// the metadata of "~/.ssh/config" is stored in "~/.ssh/config.ssh-view.json"
+pub fn metadata_file(config_file: &Path) -> PathBuf {
+    let mut file_name = config_file.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".ssh-view.json");
+    config_file.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_keep_their_shortcuts() {
+        let mut metadata = Metadata::default();
+        assert_eq!(metadata.pin_host("web"), 1);
+        assert_eq!(metadata.pin_host("db"), 2);
+        assert_eq!(metadata.pin_host("web"), 1);
+        // unpinning a host doesn't change the shortcuts of the others, its shortcut is the first one reused
+        metadata.update_host("web", |host| host.pin = None);
+        assert_eq!(metadata.pin("web"), None);
+        assert!(!metadata.hosts.contains_key("web"));
+        assert_eq!(metadata.pin("db"), Some(2));
+        assert_eq!(metadata.pin_host("app"), 1);
+    }
+
+    #[test]
+    fn unpin_keeps_the_other_settings() {
+        let mut metadata = Metadata::default();
+        metadata.update_host("web", |host| host.launcher = Some(Launcher::Mosh));
+        metadata.pin_host("web");
+        metadata.update_host("web", |host| host.pin = None);
+        assert_eq!(metadata.launcher("web"), Launcher::Mosh);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::error::CommandError;
+use crate::launch::LaunchMode;
+use crate::state::{format_timestamp, load_state, now, save_state, state_dir};
+use prettytable::{color, Attr, Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fs::{create_dir_all, remove_dir};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const MOUNTS_STATE: &str = "mounts.json";
+
+/// Remote directory mounted with sshfs, stored in the state file while it is mounted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveMount {
+    pub host: String,
+    pub remote_path: String,
+    pub mountpoint: PathBuf,
+    /// the mountpoint was created by ssh-view, so it is removed when unmounted
+    pub created: bool,
+    pub mounted: u64,
+}
+
+/// Options of the mount that don't depend on the host
+pub struct MountOptions<'a> {
+    pub command: &'a str,
+    pub args: &'a Option<Vec<String>>,
+    /// directory where the default mountpoints are created
+    pub mount_dir: &'a Option<PathBuf>,
+    pub launch: LaunchMode,
+}
+
+/// directory of the default mountpoints, SSH_VIEW_MOUNT_DIR or "mnt" in the state directory
+pub fn mount_dir(mount_dir: &Option<PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    choose_mount_dir(mount_dir, env::var_os("SSH_VIEW_MOUNT_DIR").map(PathBuf::from), state_dir)
+}
+
+/// the directory given with "--mount-dir" goes first, then the one of the environment and then the state directory
+fn choose_mount_dir(
+    mount_dir: &Option<PathBuf>,
+    env_dir: Option<PathBuf>,
+    state_dir: impl FnOnce() -> Result<PathBuf, Box<dyn Error>>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    match mount_dir.clone().or(env_dir) {
+        Some(dir) => Ok(dir),
+        None => Ok(state_dir()?.join("mnt")),
+    }
+}
+
+pub fn sshfs_command(
+    command: &str,
+    connection_name: &str,
+    remote_path: &str,
+    mountpoint: &Path,
+    args: &Option<Vec<String>>,
+) -> Command {
+    // the Host line may have several names, sshfs connects to the first one
+    let connection_name = connection_name.split_whitespace().next().unwrap_or_default();
+    let mut command = Command::new(command);
+    command
+        .arg(format!("{connection_name}:{remote_path}"))
+        .arg(mountpoint)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command
+}
+
+/// the command that unmounts a fuse file system in this platform
+pub fn unmount_command(mountpoint: &Path) -> Command {
+    let mut command = match cfg!(target_os = "linux") {
+        true => {
+            let mut command = Command::new("fusermount");
+            command.arg("-u");
+            command
+        }
+        false => Command::new("umount"),
+    };
+    command.arg(mountpoint);
+    command
+}
+
+pub fn mount(
+    connection_name: &str,
+    remote_path: &Option<String>,
+    mountpoint: &Option<PathBuf>,
+    options: &MountOptions,
+) -> Result<(), Box<dyn Error>> {
+    // the Host line may have several names, sshfs and the default mountpoint use the first one
+    let connection_name = connection_name.split_whitespace().next().unwrap_or_default();
+    let mountpoint = match mountpoint {
+        Some(mountpoint) => mountpoint.clone(),
+        None => mount_dir(options.mount_dir)?.join(connection_name),
+    };
+    let remote_path = remote_path.clone().unwrap_or_default();
+    let mut command = sshfs_command(options.command, connection_name, remote_path.as_str(), &mountpoint, options.args);
+    if !options.launch.spawns() {
+        options.launch.announce(&command);
+        return Ok(());
+    }
+    let mut mounts = active_mounts()?;
+    let mountpoint = absolute(&mountpoint)?;
+    if mounts.iter().any(|mount| mount.mountpoint == mountpoint) {
+        return Err(Box::new(CommandError::new(
+            "Mount",
+            format!("{} is already mounted", mountpoint.display()).as_str(),
+        )));
+    }
+    let created = !mountpoint.exists();
+    if created {
+        create_dir_all(&mountpoint)?;
+    }
+    let mountpoint = absolute(&mountpoint)?;
+    let status = command.spawn()?.wait()?;
+    if !status.success() {
+        if created {
+            let _ = remove_dir(&mountpoint);
+        }
+        return Err(Box::new(CommandError::new(
+            "Mount",
+            format!("{} exited with {}", options.command, status).as_str(),
+        )));
+    }
+    println!("mounted {}:{} on {}", connection_name, remote_path, mountpoint.display());
+    mounts.push(ActiveMount {
+        host: connection_name.to_string(),
+        remote_path,
+        mountpoint,
+        created,
+        mounted: now(),
+    });
+    save_state(MOUNTS_STATE, &mounts)
+}
+
+/// unmounts the mountpoint, or every mount of the host when the target is a connection name
+pub fn unmount(target: &Option<String>, all: bool, launch: &LaunchMode) -> Result<(), Box<dyn Error>> {
+    let mounts = active_mounts()?;
+    let mountpoint = target.as_ref().map(|target| absolute(Path::new(target))).transpose()?;
+    let (unmount, keep): (Vec<ActiveMount>, Vec<ActiveMount>) = mounts.into_iter().partition(|mount| {
+        all || target.as_ref() == Some(&mount.host) || mountpoint.as_ref() == Some(&mount.mountpoint)
+    });
+    if unmount.is_empty() && !all {
+        return Err(Box::new(CommandError::new(
+            "Mount",
+            format!("nothing mounted on or from {}", target.clone().unwrap_or_default()).as_str(),
+        )));
+    }
+    let mut failed = vec![];
+    for mount in unmount.into_iter() {
+        let mut command = unmount_command(&mount.mountpoint);
+        if !launch.announce(&command) {
+            continue;
+        }
+        let error = match command.output() {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => Some(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(error) = error {
+            eprintln!("couldn't unmount {}: {}", mount.mountpoint.display(), error);
+            failed.push(mount);
+            continue;
+        }
+        if mount.created {
+            let _ = remove_dir(&mount.mountpoint);
+        }
+        println!("unmounted {}", mount.mountpoint.display());
+    }
+    if !launch.spawns() {
+        return Ok(());
+    }
+    let failures = failed.len();
+    save_state(MOUNTS_STATE, &keep.into_iter().chain(failed).collect::<Vec<ActiveMount>>())?;
+    if failures > 0 {
+        return Err(Box::new(CommandError::new("Mount", format!("{failures} mounts couldn't be unmounted").as_str())));
+    }
+    Ok(())
+}
+
+/// returns the mounts of the state file that are still mounted, the other ones are cleaned up
+fn active_mounts() -> Result<Vec<ActiveMount>, Box<dyn Error>> {
+    let mounts: Vec<ActiveMount> = load_state(MOUNTS_STATE)?;
+    let (active, stale): (Vec<ActiveMount>, Vec<ActiveMount>) = mounts
+        .into_iter()
+        .partition(|mount| is_mounted(&mount.mountpoint));
+    if !stale.is_empty() {
+        for mount in stale.iter() {
+            println!("{} is not mounted anymore, removing it", mount.mountpoint.display());
+            if mount.created {
+                let _ = remove_dir(&mount.mountpoint);
+            }
+        }
+        save_state(MOUNTS_STATE, &active)?;
+    }
+    Ok(active)
+}
+
+pub fn print_mounts() -> Result<(), Box<dyn Error>> {
+    let mounts = active_mounts()?;
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Host", "Remote Path", "Mountpoint", "Mounted"]
+            .iter()
+            .map(|title| {
+                Cell::new(title)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::GREEN))
+            })
+            .collect::<Vec<Cell>>(),
+    ));
+    for mount in mounts.iter() {
+        table.add_row(Row::new(
+            [
+                mount.host.clone(),
+                mount.remote_path.clone(),
+                mount.mountpoint.display().to_string(),
+                format_timestamp(mount.mounted),
+            ]
+                .iter()
+                .map(|cell| Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(color::CYAN)))
+                .collect::<Vec<Cell>>(),
+        ));
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// resolves the path as the mount table shows it, falling back to the absolute path when it can't be read
+fn absolute(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let path = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => env::current_dir()?.join(path),
+    };
+    Ok(path.canonicalize().unwrap_or(path))
+}
+
+/// checks the mount table of the system, the escaped characters of /proc/self/mounts are decoded
+#[cfg(target_os = "linux")]
+fn is_mounted(mountpoint: &Path) -> bool {
+    std::fs::read_to_string("/proc/self/mounts")
+        .map(|mounts| mount_table_has(mounts.as_str(), mountpoint.to_string_lossy().as_ref()))
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+fn mount_table_has(mounts: &str, mountpoint: &str) -> bool {
+    mounts.lines().any(|line| {
+        line.split(' ').nth(1).is_some_and(|path| {
+            path.replace("\\040", " ").replace("\\011", "\t").replace("\\134", "\\") == mountpoint
+        })
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_mounted(mountpoint: &Path) -> bool {
+    Command::new("mount")
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout).contains(format!(" on {} ", mountpoint.display()).as_str())
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sshfs_mounts_the_first_alias() {
+        let args = Some(vec![String::from("-o"), String::from("reconnect")]);
+        let command = sshfs_command("sshfs", "web web.prod", "/var/log", Path::new("/mnt/web"), &args);
+        assert_eq!(command.get_program(), "sshfs");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["web:/var/log", "/mnt/web", "-o", "reconnect"]);
+    }
+
+    #[test]
+    fn mount_dir_precedence() {
+        let state_dir = || Ok(PathBuf::from("/home/me/.ssh-view"));
+        let flag = Some(PathBuf::from("/mnt/flag"));
+        let env_dir = Some(PathBuf::from("/mnt/env"));
+        assert_eq!(choose_mount_dir(&flag, env_dir.clone(), state_dir).unwrap(), PathBuf::from("/mnt/flag"));
+        assert_eq!(choose_mount_dir(&None, env_dir, state_dir).unwrap(), PathBuf::from("/mnt/env"));
+        assert_eq!(choose_mount_dir(&None, None, state_dir).unwrap(), PathBuf::from("/home/me/.ssh-view/mnt"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn mount_table_decodes_the_escaped_paths() {
+        let mounts = "proc /proc proc rw 0 0\n\
+                      web:/var/log /home/me/my\\040mounts/web\\011logs fuse.sshfs rw 0 0\n";
+        assert!(mount_table_has(mounts, "/home/me/my mounts/web\tlogs"));
+        assert!(!mount_table_has(mounts, "/home/me/my\\040mounts/web\\011logs"));
+        assert!(!mount_table_has(mounts, "/home/me/my mounts"));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::annotations::{read_annotations, write_annotations};
+use crate::error::CommandError;
+use crate::launch::LaunchMode;
+use std::env;
+use std::error::Error;
+use std::fs::{read_to_string, remove_file, File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// key of the structured comment with the one line description of a host
+pub const DESCRIPTION: &str = "description";
+/// key of the structured comments with the notes of a host, one comment for each line
+pub const NOTE: &str = "note";
+
+/// the editor of VISUAL or EDITOR, with its arguments
+pub fn editor_command(file: &Path) -> Command {
+    let editor = env::var("VISUAL")
+        .or(env::var("EDITOR"))
+        .unwrap_or(String::from(if cfg!(windows) { "notepad" } else { "vi" }));
+    let mut words = editor.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or("vi"));
+    command.args(words).arg(file);
+    command
+}
+
+/// opens the description and the notes of the Host block with the index in the editor and saves them back
+pub fn edit_notes(config_file: &Path, index: usize, connection_name: &str, launch: &LaunchMode) -> Result<(), Box<dyn Error>> {
+    if !launch.spawns() {
+        launch.announce(&editor_command(&editor_file(0)));
+        return Ok(());
+    }
+    let description = read_annotations(config_file, index, DESCRIPTION)?;
+    let description = description.first().cloned().unwrap_or_default();
+    let notes = read_annotations(config_file, index, NOTE)?;
+    let contents = editor_contents(connection_name, description.as_str(), &notes);
+    let (file, mut private_file) = create_editor_file()?;
+    private_file.write_all(contents.as_bytes())?;
+    drop(private_file);
+    let status = editor_command(&file).status();
+    let edited = read_to_string(&file);
+    let _ = remove_file(&file);
+    if !status?.success() {
+        return Err(Box::new(CommandError::new("Note", "the editor failed, the notes weren't changed")));
+    }
+    let (edited_description, edited_notes) = parse_edited(edited?.as_str());
+    if edited_description == description && edited_notes == notes {
+        println!("the notes of {connection_name} weren't changed");
+        return Ok(());
+    }
+    set_notes(config_file, index, edited_description.as_str(), &edited_notes)
+}
+
+/// the text opened in the editor, the description in the first line and the notes after an empty line
+fn editor_contents(connection_name: &str, description: &str, notes: &[String]) -> String {
+    let mut contents = format!("{description}\n\n");
+    for note in notes.iter() {
+        contents.push_str(format!("{note}\n").as_str());
+    }
+    contents.push_str(
+        format!("# The first line is the description of {connection_name} and the following ones its notes.\n").as_str(),
+    );
+    contents.push_str("# Lines starting with \"#\" are ignored.\n");
+    contents
+}
+
+/// reads the description and the notes back from the text of the editor, without the comments and the empty lines
+/// around the notes, the lines are trimmed as the structured comments are when they are read
+fn parse_edited(edited: &str) -> (String, Vec<String>) {
+    let lines = edited
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.trim())
+        .collect::<Vec<&str>>();
+    let description = lines.first().copied().unwrap_or_default();
+    let notes = lines
+        .iter()
+        .skip(1)
+        .skip_while(|line| line.is_empty())
+        .map(|line| line.to_string())
+        .collect::<Vec<String>>();
+    (description.to_string(), trim_empty_lines(notes))
+}
+
+/// path of the file for the editor, the attempt changes the name when a file of an earlier run is left
+fn editor_file(attempt: u32) -> PathBuf {
+    env::temp_dir().join(format!("ssh-view-note-{}-{}.txt", std::process::id(), attempt))
+}
+
+/// creates the file for the editor with the first name that isn't taken
+fn create_editor_file() -> io::Result<(PathBuf, File)> {
+    let mut attempt = 0;
+    loop {
+        let file = editor_file(attempt);
+        match create_private_file(&file) {
+            Ok(private_file) => return Ok((file, private_file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// creates the file for the editor readable only by the user, failing if it exists so another user can't prepare it
+/// to read the notes or to make the editor write somewhere else through a link
+fn create_private_file(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn set_notes(config_file: &Path, index: usize, description: &str, notes: &[String]) -> Result<(), Box<dyn Error>> {
+    let description = match description.is_empty() {
+        true => vec![],
+        false => vec![description.to_string()],
+    };
+    // every write puts the comments right after the Host line, so the notes go first to end below the description
+    write_annotations(config_file, index, NOTE, notes)?;
+    write_annotations(config_file, index, DESCRIPTION, &description)
+}
+
+fn trim_empty_lines(mut lines: Vec<String>) -> Vec<String> {
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempFile;
+
+    #[test]
+    fn parse_the_edited_notes() {
+        let (description, notes) = parse_edited(
+            "  Primary database  \n\n\n# a comment\nrestart with systemctl\n\n  runs on port 5432  \n\n\n#@ignored\n",
+        );
+        assert_eq!(description, "Primary database");
+        assert_eq!(notes, ["restart with systemctl", "", "runs on port 5432"]);
+    }
+
+    #[test]
+    fn parse_empty_notes() {
+        assert_eq!(parse_edited(""), (String::new(), vec![]));
+        assert_eq!(parse_edited("# only comments\n\n"), (String::new(), vec![]));
+        assert_eq!(parse_edited("\nfirst note\n"), (String::new(), vec![String::from("first note")]));
+    }
+
+    #[test]
+    fn unchanged_notes_read_back_the_same() {
+        let notes = vec![String::from("restart with systemctl"), String::new(), String::from("runs on port 5432")];
+        let contents = editor_contents("db", "Primary database", &notes);
+        assert_eq!(parse_edited(contents.as_str()), (String::from("Primary database"), notes));
+        let contents = editor_contents("db", "", &[]);
+        assert_eq!(parse_edited(contents.as_str()), (String::new(), vec![]));
+    }
+
+    #[test]
+    fn edited_notes_read_back_from_the_config_file() {
+        let file = TempFile::new("notes", "Host web\n    HostName 10.0.0.1\nHost db\n    HostName 10.0.0.2\n");
+        let path = file.path();
+        let (description, notes) = parse_edited("Primary database\n\n  restart with systemctl\n\n\tport 5432\n");
+        set_notes(path, 1, description.as_str(), &notes).unwrap();
+        let read_description = read_annotations(path, 1, DESCRIPTION).unwrap();
+        let read_notes = read_annotations(path, 1, NOTE).unwrap();
+        assert_eq!(read_description, [description.as_str()]);
+        assert_eq!(read_notes, notes);
+        // opening the editor again and saving it without changes leaves the notes as they are
+        let contents = editor_contents("db", read_description[0].as_str(), &read_notes);
+        assert_eq!(parse_edited(contents.as_str()), (description, notes));
+    }
+
+    #[test]
+    fn editor_file_skips_the_files_left_by_other_runs() {
+        let stale = editor_file(0);
+        let _ = create_private_file(&stale);
+        let (file, _) = create_editor_file().unwrap();
+        assert_ne!(file, stale);
+        let _ = remove_file(&file);
+        let _ = remove_file(&stale);
+    }
+}
@@ -0,0 +1,319 @@
+use crate::import::ImportedHost;
+use std::collections::HashMap;
+
+/// key of the registry with the saved sessions of PuTTY
+const SESSIONS_KEY: &str = r"\Software\SimonTatham\PuTTY\Sessions\";
+
+/// Value of the registry export, PuTTY saves numbers as dwords and the rest as strings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegValue {
+    String(String),
+    Dword(u32),
+}
+
+/// Saved session of a PuTTY registry export
+#[derive(Debug, Default)]
+pub struct PuttySession {
+    pub name: String,
+    pub values: HashMap<String, RegValue>,
+}
+
+impl PuttySession {
+    fn string(&self, key: &str) -> Option<&str> {
+        match self.values.get(key) {
+            Some(RegValue::String(value)) if !value.is_empty() => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    fn dword(&self, key: &str) -> Option<u32> {
+        match self.values.get(key) {
+            Some(RegValue::Dword(value)) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// regedit writes the exports in UTF-16 with a byte order mark, older exports are plain text
+pub fn decode_export(bytes: &[u8]) -> String {
+    match bytes {
+        [0xff, 0xfe, rest @ ..] => String::from_utf16_lossy(
+            &rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect::<Vec<u16>>(),
+        ),
+        _ => String::from_utf8_lossy(bytes.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(bytes)).to_string(),
+    }
+}
+
+/// reads the sessions of the export, the keys of other programs are ignored
+pub fn parse_sessions(data: &str) -> Vec<PuttySession> {
+    let mut sessions: Vec<PuttySession> = vec![];
+    let mut in_session = false;
+    for line in data.lines() {
+        let line = line.trim();
+        if let Some(key) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            in_session = false;
+            if let Some((_, name)) = key.split_once(SESSIONS_KEY).filter(|_| !key.starts_with('-')) {
+                if !name.is_empty() && !name.contains('\\') {
+                    sessions.push(PuttySession {
+                        name: decode_session_name(name),
+                        values: HashMap::new(),
+                    });
+                    in_session = true;
+                }
+            }
+            continue;
+        }
+        if let (true, Some(session), Some((key, value))) = (in_session, sessions.last_mut(), parse_value(line)) {
+            session.values.insert(key, value);
+        }
+    }
+    sessions
+}
+
+/// parses "\"<Key>\"=\"<Value>\"" and "\"<Key>\"=dword:<Hex>" lines, binary values aren't used by PuTTY sessions
+fn parse_value(line: &str) -> Option<(String, RegValue)> {
+    let (key, rest) = parse_string(line)?;
+    let value = rest.strip_prefix('=')?;
+    match value.strip_prefix("dword:") {
+        Some(hex) => Some((key, RegValue::Dword(u32::from_str_radix(hex.trim(), 16).ok()?))),
+        None => parse_string(value).map(|(value, _)| (key, RegValue::String(value))),
+    }
+}
+
+/// reads a quoted string of the export, returning it without the escapes and the text after it
+fn parse_string(text: &str) -> Option<(String, &str)> {
+    let text = text.strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?.1),
+            '"' => return Some((value, &text[position + 1..])),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// PuTTY escapes the session names like urls, "my%20server" is "my server"
+fn decode_session_name(name: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match (byte, tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// maps the settings of the session to ssh_config options, the warnings are the settings that couldn't be mapped exactly
+pub fn session_host(session: &PuttySession, name: &str) -> Result<(ImportedHost, Vec<String>), String> {
+    if let Some(protocol) = session.string("Protocol").filter(|protocol| *protocol != "ssh") {
+        return Err(format!("it uses {protocol} instead of ssh"));
+    }
+    let host_name = session.string("HostName").ok_or("it has no HostName")?;
+    // PuTTY accepts "user@host" as the host name
+    let (user, host_name) = match host_name.rsplit_once('@') {
+        Some((user, host_name)) => (Some(user), host_name),
+        None => (session.string("UserName"), host_name),
+    };
+    let mut warnings = vec![];
+    let mut options = vec![(String::from("HostName"), host_name.to_string())];
+    if let Some(user) = user {
+        options.push((String::from("user"), user.to_string()));
+    }
+    options.push((String::from("port"), session.dword("PortNumber").unwrap_or(22).to_string()));
+    if let Some(key_file) = session.string("PublicKeyFile") {
+        let identity_file = key_file.strip_suffix(".ppk").unwrap_or(key_file);
+        warnings.push(format!(
+            "the key {key_file} has to be converted with \"puttygen {key_file} -O private-openssh -o {identity_file}\""
+        ));
+        options.push((String::from("IdentityFile"), identity_file.to_string()));
+    }
+    if let Some((key, value)) = proxy_option(session, &mut warnings) {
+        options.push((key, value));
+    }
+    for (key, setting) in [("ForwardAgent", "AgentFwd"), ("ForwardX11", "X11Forward"), ("Compression", "Compression")] {
+        if session.dword(setting) == Some(1) {
+            options.push((String::from(key), String::from("yes")));
+        }
+    }
+    if let Some(forwardings) = session.string("PortForwardings") {
+        for forwarding in forwardings.split(',').filter(|forwarding| !forwarding.is_empty()) {
+            match forward_option(forwarding) {
+                Some(option) => options.push(option),
+                None => warnings.push(format!("the port forwarding \"{forwarding}\" couldn't be read")),
+            }
+        }
+    }
+    Ok((
+        ImportedHost {
+            name: name.to_string(),
+            options,
+            tags: vec![],
+            annotations: vec![],
+        },
+        warnings,
+    ))
+}
+
+/// maps the proxy of the session, PuTTY connects to the proxy itself so ssh needs a ProxyCommand or a ProxyJump
+fn proxy_option(session: &PuttySession, warnings: &mut Vec<String>) -> Option<(String, String)> {
+    let method = session.dword("ProxyMethod").unwrap_or(0);
+    if method == 0 {
+        return None;
+    }
+    let host = session.string("ProxyHost").unwrap_or("proxy");
+    let port = session.dword("ProxyPort").unwrap_or(80);
+    let proxy_command = |kind: &str| Some((String::from("ProxyCommand"), format!("nc -X {kind} -x {host}:{port} %h %p")));
+    if session.string("ProxyUsername").is_some() && [1, 2, 3].contains(&method) {
+        warnings.push(String::from("the proxy user isn't imported, nc asks for it when it connects"));
+    }
+    match method {
+        1 => proxy_command("4"),
+        2 => proxy_command("5"),
+        3 => proxy_command("connect"),
+        5 => session.string("ProxyTelnetCommand").map(|command| {
+            let command = command.replace("%host", "%h").replace("%port", "%p");
+            (String::from("ProxyCommand"), command)
+        }),
+        6 => {
+            // the proxy is an ssh server, which listens on 22 when PuTTY doesn't have the port
+            let address = match session.dword("ProxyPort") {
+                None | Some(22) => host.to_string(),
+                Some(port) if host.contains(':') => format!("[{host}]:{port}"),
+                Some(port) => format!("{host}:{port}"),
+            };
+            let jump = match session.string("ProxyUsername") {
+                Some(user) => format!("{user}@{address}"),
+                None => address,
+            };
+            Some((String::from("ProxyJump"), jump))
+        }
+        _ => {
+            warnings.push(format!("the proxy of type {method} has no equivalent in ssh"));
+            None
+        }
+    }
+}
+
+/// maps a port forwarding of PuTTY, "[4|6](L|R)<ListenPort>=<Host>:<Port>" or "[4|6]D<ListenPort>",
+/// older versions separate the destination with a tab
+fn forward_option(forwarding: &str) -> Option<(String, String)> {
+    let forwarding = forwarding.trim_start_matches(['4', '6']).replace('\t', "=");
+    let kind = forwarding.get(..1)?;
+    let rest = &forwarding[1..];
+    let (listen, destination) = match rest.split_once('=') {
+        Some((listen, destination)) => (listen, Some(destination)),
+        None => (rest, None),
+    };
+    if listen.is_empty() {
+        return None;
+    }
+    match (kind, destination.filter(|destination| !destination.is_empty())) {
+        ("L", Some(destination)) => Some((String::from("LocalForward"), format!("{listen} {destination}"))),
+        ("R", Some(destination)) => Some((String::from("RemoteForward"), format!("{listen} {destination}"))),
+        ("D", _) => Some((String::from("DynamicForward"), listen.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\Default%20Settings]
+"HostName"=""
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\prod%20web]
+"HostName"="deploy@10.1.0.5"
+"PortNumber"=dword:00000016
+"PublicKeyFile"="C:\\keys\\prod.ppk"
+"AgentFwd"=dword:00000001
+"PortForwardings"="L8080=localhost:80,4D1080,R9000=db:5432,Lbad"
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\SshHostKeys]
+"rsa2@22:10.1.0.5"="0x23"
+"#;
+
+    #[test]
+    fn parse_sessions_of_an_export() {
+        let sessions = parse_sessions(EXPORT);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].name, "Default Settings");
+        assert_eq!(sessions[1].name, "prod web");
+        assert_eq!(sessions[1].values.get("PortNumber"), Some(&RegValue::Dword(22)));
+        assert_eq!(
+            sessions[1].values.get("PublicKeyFile"),
+            Some(&RegValue::String(String::from(r"C:\keys\prod.ppk")))
+        );
+    }
+
+    #[test]
+    fn decode_utf16_exports() {
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend("[a]\r\n".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        assert_eq!(decode_export(&bytes), "[a]\r\n");
+        assert_eq!(decode_export(b"\xef\xbb\xbf[a]"), "[a]");
+    }
+
+    #[test]
+    fn session_host_maps_the_settings() {
+        let sessions = parse_sessions(EXPORT);
+        assert_eq!(session_host(&sessions[0], "default").err().as_deref(), Some("it has no HostName"));
+        let (host, warnings) = session_host(&sessions[1], "prod-web").unwrap();
+        let options = host.options.iter().map(|(key, value)| format!("{key} {value}")).collect::<Vec<String>>();
+        assert_eq!(
+            options,
+            [
+                "HostName 10.1.0.5",
+                "user deploy",
+                "port 22",
+                r"IdentityFile C:\keys\prod",
+                "ForwardAgent yes",
+                "LocalForward 8080 localhost:80",
+                "DynamicForward 1080",
+                "RemoteForward 9000 db:5432",
+            ]
+        );
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn proxy_option_of_ssh_proxies() {
+        let jump = |values: &str| {
+            let export = format!("[{SESSIONS_KEY}jump]\n\"ProxyMethod\"=dword:00000006\n{values}");
+            let session = parse_sessions(export.as_str()).remove(0);
+            proxy_option(&session, &mut vec![]).map(|(_, jump)| jump)
+        };
+        assert_eq!(jump("\"ProxyHost\"=\"bastion\"\n").as_deref(), Some("bastion"));
+        assert_eq!(jump("\"ProxyHost\"=\"bastion\"\n\"ProxyPort\"=dword:00000016\n").as_deref(), Some("bastion"));
+        assert_eq!(
+            jump("\"ProxyHost\"=\"bastion\"\n\"ProxyPort\"=dword:00000898\n\"ProxyUsername\"=\"me\"\n").as_deref(),
+            Some("me@bastion:2200")
+        );
+        assert_eq!(jump("\"ProxyHost\"=\"::1\"\n\"ProxyPort\"=dword:00000898\n").as_deref(), Some("[::1]:2200"));
+    }
+
+    #[test]
+    fn forward_option_of_older_versions() {
+        assert_eq!(
+            forward_option("6L8080\tlocalhost:80"),
+            Some((String::from("LocalForward"), String::from("8080 localhost:80")))
+        );
+        assert_eq!(forward_option("L8080"), None);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::ssh_config::{
+    effective_address, effective_option, effective_options, is_pattern, option_line, split_destination, SshHost,
+};
+use clap::ValueEnum;
+use std::process::{Command, Stdio};
+
+/// What is exported for the selected connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportAs {
+    /// the command using the alias of the connection
+    Command,
+    /// the standalone Host blocks of the connection and its ProxyJump hosts
+    Config,
+    /// the command with every option of the connection, it doesn't need the alias to work
+    CommandExpanded,
+    /// an ssh://user@host:port URI
+    Uri,
+}
+
+/// returns the Host block of the alias with its effective options, followed by the blocks of its ProxyJump hosts
+pub fn host_config(hosts: &[SshHost], alias: &str) -> String {
+    let mut aliases = vec![alias.to_string()];
+    let mut blocks = vec![];
+    while let Some(alias) = aliases.pop() {
+        let options = effective_options(hosts, alias.as_str());
+        let mut block = format!("Host {alias}\n");
+        for (key, value) in &options {
+            block.push_str(format!("    {}\n", option_line(key, value)).as_str());
+        }
+        blocks.push(block);
+        for jump in jump_hosts(&options) {
+            let (_, jump, _) = split_destination(jump.as_str());
+            let known = blocks.iter().any(|block| block.starts_with(format!("Host {jump}\n").as_str()));
+            let configured = hosts
+                .iter()
+                .any(|host| !is_pattern(host.name.as_str()) && host.matches(jump.as_str()));
+            if configured && !known && !aliases.contains(&jump) {
+                aliases.insert(0, jump);
+            }
+        }
+    }
+    blocks.join("\n").trim_end().to_string()
+}
+
+/// builds the command with the effective options of the alias as arguments, so it works without the config file
+pub fn expanded_command(command: &str, hosts: &[SshHost], alias: &str, args: &Option<Vec<String>>) -> Command {
+    let mut command = Command::new(command);
+    let options = effective_options(hosts, alias);
+    let (_, port) = effective_address(hosts, alias);
+    if port != 22 {
+        command.arg("-p").arg(port.to_string());
+    }
+    for (key, value) in &options {
+        match key.to_ascii_lowercase().as_str() {
+            "hostname" | "user" | "port" | "proxyjump" => {}
+            "identityfile" => {
+                command.arg("-i").arg(value);
+            }
+            _ => {
+                command.arg("-o").arg(format!("{key}={value}"));
+            }
+        }
+    }
+    let jumps = expand_jumps(hosts, &options, 0);
+    if !jumps.is_empty() {
+        command.arg("-J").arg(jumps.join(","));
+    }
+    command
+        .arg(destination(hosts, alias))
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command
+}
+
+/// returns the ssh://user@host:port URI of the alias
+pub fn uri(hosts: &[SshHost], alias: &str) -> String {
+    let (host_name, port) = effective_address(hosts, alias);
+    let user = effective_option(hosts, alias, "User")
+        .map(|user| format!("{user}@"))
+        .unwrap_or_default();
+    let host_name = match host_name.contains(':') {
+        true => format!("[{host_name}]"),
+        false => host_name,
+    };
+    match port {
+        22 => format!("ssh://{user}{host_name}"),
+        port => format!("ssh://{user}{host_name}:{port}"),
+    }
+}
+
+/// returns the hosts of the ProxyJump option, "none" means there is no jump host
+fn jump_hosts(options: &[(String, String)]) -> Vec<String> {
+    options
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("ProxyJump"))
+        .filter(|(_, value)| !value.eq_ignore_ascii_case("none"))
+        .map(|(_, value)| value.split(',').map(|jump| jump.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// expands the ProxyJump chain, including the jump hosts of the jump hosts as ssh does
+fn expand_jumps(hosts: &[SshHost], options: &[(String, String)], depth: usize) -> Vec<String> {
+    if depth > 8 {
+        return vec![];
+    }
+    let mut jumps = vec![];
+    for jump in jump_hosts(options) {
+        let (_, alias, _) = split_destination(jump.as_str());
+        jumps.extend(expand_jumps(hosts, &effective_options(hosts, alias.as_str()), depth + 1));
+        jumps.push(expand_jump(hosts, jump.as_str()));
+    }
+    jumps
+}
+
+/// replaces the alias of a ProxyJump host with its user, HostName and Port
+fn expand_jump(hosts: &[SshHost], jump: &str) -> String {
+    let (user, alias, port) = split_destination(jump);
+    let user = user.or(effective_option(hosts, alias.as_str(), "User"));
+    let (host_name, default_port) = effective_address(hosts, alias.as_str());
+    let host_name = match user {
+        Some(user) => format!("{user}@{host_name}"),
+        None => host_name,
+    };
+    match port.unwrap_or(default_port) {
+        22 => host_name,
+        port if host_name.contains(':') => format!("[{host_name}]:{port}"),
+        port => format!("{host_name}:{port}"),
+    }
+}
+
+/// returns the user@host destination of the alias
+fn destination(hosts: &[SshHost], alias: &str) -> String {
+    let (host_name, _) = effective_address(hosts, alias);
+    match effective_option(hosts, alias, "User") {
+        Some(user) => format!("{user}@{host_name}"),
+        None => host_name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh_config::parse_ssh_hosts;
+
+    #[test]
+    fn host_config_quotes_only_paths() {
+        let hosts = parse_ssh_hosts(
+            "Host web\n    HostName 10.0.0.1\n    IdentityFile \"~/my keys/id\"\n    ProxyCommand ssh -W %h:%p bastion\n",
+        );
+        assert_eq!(
+            host_config(&hosts, "web"),
+            "Host web\n    HostName 10.0.0.1\n    IdentityFile \"~/my keys/id\"\n    ProxyCommand ssh -W %h:%p bastion"
+        );
+    }
+
+    #[test]
+    fn uri_uses_the_effective_options_of_the_alias() {
+        let hosts = parse_ssh_hosts("Host web web.prod\n    HostName 10.0.0.1\n    Port 2222\nHost *\n    User admin\n");
+        assert_eq!(uri(&hosts, "web"), "ssh://admin@10.0.0.1:2222");
+        assert_eq!(uri(&hosts, "web.prod"), "ssh://admin@10.0.0.1:2222");
+        let hosts = parse_ssh_hosts("Host v6\n    HostName 2001:db8::1\n");
+        assert_eq!(uri(&hosts, "v6"), "ssh://[2001:db8::1]");
+    }
+}
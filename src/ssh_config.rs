@@ -0,0 +1,370 @@
+use crate::annotations::annotation;
+use std::env;
+use std::error::Error;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+/// ssh refuses to follow Include deeper than this, it also stops include loops
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Host block of the ssh config file with its options in the same order as in the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshHost {
+    pub index: usize,
+    pub name: String,
+    pub options: Vec<(String, String)>,
+    /// structured comments of ssh-view in the block, "#@<key> <value>"
+    pub annotations: Vec<(String, String)>,
+}
+
+impl SshHost {
+    /// returns the first value of the option, the keyword is case insensitive as in ssh
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| option.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// checks if the alias matches any of the patterns of the Host line
+    pub fn matches(&self, alias: &str) -> bool {
+        let mut matched = false;
+        for pattern in self.name.split_whitespace() {
+            match pattern.strip_prefix('!') {
+                Some(pattern) => {
+                    if glob_match(pattern, alias) {
+                        return false;
+                    }
+                }
+                None => matched = matched || glob_match(pattern, alias),
+            }
+        }
+        matched
+    }
+}
+
+/// reads every Host block of the ssh config file and of the files it includes, in the order ssh reads them,
+/// the index of each host is the same one shown in the table
+pub fn read_ssh_hosts(path: &Path) -> Result<Vec<SshHost>, Box<dyn Error>> {
+    let data = read_to_string(path)?;
+    let files = included_files(path);
+    // the table lists the hosts of the config file first and then the ones of each included file
+    let mut offsets = vec![parse_ssh_hosts(data.as_str()).len()];
+    for file in files.iter() {
+        let hosts = read_to_string(file).map(|data| parse_ssh_hosts(data.as_str()).len()).unwrap_or_default();
+        offsets.push(offsets[offsets.len() - 1] + hosts);
+    }
+    offsets.insert(0, 0);
+    let mut hosts = vec![];
+    let mut visited = vec![];
+    read_included_hosts(data.as_str(), 0, &files, &offsets, &mut visited, &mut hosts);
+    Ok(hosts)
+}
+
+fn read_included_hosts(
+    data: &str,
+    file: usize,
+    files: &[PathBuf],
+    offsets: &[usize],
+    visited: &mut Vec<usize>,
+    hosts: &mut Vec<SshHost>,
+) {
+    let mut index = offsets[file];
+    for line in data.lines() {
+        let line = line.trim();
+        if line.starts_with("Host ") {
+            hosts.push(SshHost {
+                index,
+                name: line.replace("Host ", "").trim().to_string(),
+                options: vec![],
+                annotations: vec![],
+            });
+            index += 1;
+        } else if let Some((key, value)) = split_option(line) {
+            if key.eq_ignore_ascii_case("Include") {
+                for included in value.split_whitespace().flat_map(expand_include) {
+                    let Some(position) = files.iter().position(|file| *file == included) else {
+                        continue;
+                    };
+                    if !visited.contains(&position) {
+                        visited.push(position);
+                        if let Ok(data) = read_to_string(&included) {
+                            read_included_hosts(data.as_str(), position + 1, files, offsets, visited, hosts);
+                        }
+                    }
+                }
+            } else if let Some(host) = hosts.last_mut() {
+                host.options.push((key, value));
+            }
+        } else if let (Some((key, value)), Some(host)) = (annotation(line), hosts.last_mut()) {
+            host.annotations.push((key.to_string(), value.to_string()));
+        }
+    }
+}
+
+/// returns the file with the Host block of the table index and the index of the block in that file,
+/// the writers edit the block in the file it is in instead of the config file
+pub fn block_file(path: &Path, index: usize) -> Result<(PathBuf, usize), Box<dyn Error>> {
+    let mut first = parse_ssh_hosts(read_to_string(path)?.as_str()).len();
+    if index < first {
+        return Ok((path.to_path_buf(), index));
+    }
+    for file in included_files(path) {
+        let hosts = read_to_string(&file).map(|data| parse_ssh_hosts(data.as_str()).len()).unwrap_or_default();
+        if index < first + hosts {
+            return Ok((file, index - first));
+        }
+        first += hosts;
+    }
+    Err(format!("there is no host with the index {index}").into())
+}
+
+/// returns the files added with Include by the config file and by the files they include, each one once
+pub fn included_files(path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    add_included_files(path, &mut files, 0);
+    files
+}
+
+fn add_included_files(path: &Path, files: &mut Vec<PathBuf>, depth: usize) {
+    if depth >= MAX_INCLUDE_DEPTH {
+        return;
+    }
+    let Ok(data) = read_to_string(path) else {
+        return;
+    };
+    for (key, value) in data.lines().filter_map(split_option) {
+        if !key.eq_ignore_ascii_case("Include") {
+            continue;
+        }
+        for file in value.split_whitespace().flat_map(expand_include) {
+            if file.is_file() && file != path && !files.contains(&file) {
+                files.push(file.clone());
+                add_included_files(&file, files, depth + 1);
+            }
+        }
+    }
+}
+
+/// expands the wildcards of an Include path, relative paths are taken from ~/.ssh as ssh does for user config files
+fn expand_include(pattern: &str) -> Vec<PathBuf> {
+    #[allow(deprecated)]
+    let home = env::home_dir().unwrap_or_default();
+    let path = match pattern.strip_prefix("~/") {
+        Some(path) => home.join(path),
+        None if Path::new(pattern).is_absolute() => PathBuf::from(pattern),
+        None => home.join(".ssh").join(pattern),
+    };
+    let mut paths = vec![PathBuf::new()];
+    for component in path.components() {
+        let name = component.as_os_str().to_string_lossy();
+        if !name.contains(['*', '?']) {
+            paths.iter_mut().for_each(|path| path.push(component));
+            continue;
+        }
+        paths = paths
+            .into_iter()
+            .flat_map(|dir| {
+                let mut entries = read_dir(&dir)
+                    .map(|entries| {
+                        entries
+                            .filter_map(Result::ok)
+                            .map(|entry| entry.file_name().to_string_lossy().to_string())
+                            .filter(|entry| !entry.starts_with('.') && glob_match(&name, entry))
+                            .collect::<Vec<String>>()
+                    })
+                    .unwrap_or_default();
+                entries.sort();
+                entries.into_iter().map(move |entry| dir.join(entry))
+            })
+            .collect();
+    }
+    paths
+}
+
+pub fn parse_ssh_hosts(data: &str) -> Vec<SshHost> {
+    let mut hosts: Vec<SshHost> = vec![];
+    for line in data.lines() {
+        let line = line.trim();
+        if line.starts_with("Host ") {
+            hosts.push(SshHost {
+                index: hosts.len(),
+                name: line.replace("Host ", "").trim().to_string(),
+                options: vec![],
+                annotations: vec![],
+            });
+        } else if let Some((key, value)) = split_option(line) {
+            if let Some(host) = hosts.last_mut() {
+                host.options.push((key, value));
+            }
+        } else if let (Some((key, value)), Some(host)) = (annotation(line), hosts.last_mut()) {
+            host.annotations.push((key.to_string(), value.to_string()));
+        }
+    }
+    hosts
+}
+
+/// splits a config line in keyword and value, accepting both "Key Value" and "Key=Value"
+pub fn split_option(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let key = &line[..end];
+    let value = line[end..].trim_start();
+    let value = value.strip_prefix('=').unwrap_or(value).trim();
+    Some((key.to_string(), value.trim_matches('"').to_string()))
+}
+
+/// options with a single path as value, the other ones with spaces have several arguments or are a command
+const PATH_OPTIONS: [&str; 4] = ["IdentityFile", "CertificateFile", "IdentityAgent", "UserKnownHostsFile"];
+
+/// returns the "Key Value" config line of the option, quoting only the paths with spaces
+pub fn option_line(key: &str, value: &str) -> String {
+    let quote = value.contains(char::is_whitespace)
+        && !value.starts_with('"')
+        && PATH_OPTIONS.iter().any(|option| option.eq_ignore_ascii_case(key));
+    match quote {
+        true => format!("{key} \"{value}\""),
+        false => format!("{key} {value}"),
+    }
+}
+
+/// returns the effective value of the option for the alias, the first value found in the matching blocks wins as in ssh
+pub fn effective_option(hosts: &[SshHost], alias: &str, key: &str) -> Option<String> {
+    hosts
+        .iter()
+        .filter(|host| host.matches(alias))
+        .find_map(|host| host.option(key))
+        .map(|value| value.replace("%h", alias))
+}
+
+/// options that ssh takes from every matching block instead of keeping only the first value
+const CUMULATIVE_OPTIONS: [&str; 6] = [
+    "IdentityFile",
+    "CertificateFile",
+    "LocalForward",
+    "RemoteForward",
+    "DynamicForward",
+    "SendEnv",
+];
+
+/// returns every effective option for the alias in the order ssh finds them
+pub fn effective_options(hosts: &[SshHost], alias: &str) -> Vec<(String, String)> {
+    let mut options: Vec<(String, String)> = vec![];
+    for host in hosts.iter().filter(|host| host.matches(alias)) {
+        for (key, value) in &host.options {
+            let cumulative = CUMULATIVE_OPTIONS.iter().any(|option| option.eq_ignore_ascii_case(key));
+            if cumulative || !options.iter().any(|(option, _)| option.eq_ignore_ascii_case(key)) {
+                let value = match key.eq_ignore_ascii_case("HostName") {
+                    true => value.replace("%h", alias),
+                    false => value.clone(),
+                };
+                options.push((key.clone(), value));
+            }
+        }
+    }
+    options
+}
+
+/// returns the effective HostName and Port the alias connects to
+pub fn effective_address(hosts: &[SshHost], alias: &str) -> (String, u16) {
+    let host_name = effective_option(hosts, alias, "HostName").unwrap_or(alias.to_string());
+    let port = effective_option(hosts, alias, "Port")
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(22);
+    (host_name, port)
+}
+
+/// splits a "[user@]host[:port]" destination as used by ProxyJump
+pub fn split_destination(destination: &str) -> (Option<String>, String, Option<u16>) {
+    let (user, address) = match destination.rsplit_once('@') {
+        Some((user, address)) => (Some(user.to_string()), address),
+        None => (None, destination),
+    };
+    let (host, port) = match address.strip_prefix('[').and_then(|address| address.split_once(']')) {
+        Some((host, port)) => (host, port.strip_prefix(':')),
+        None => match address.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => (host, Some(port)),
+            _ => (address, None),
+        },
+    };
+    (user, host.to_string(), port.and_then(|port| port.parse::<u16>().ok()))
+}
+
+/// checks if the Host line has patterns, so it can't be used as a connection name
+pub fn is_pattern(name: &str) -> bool {
+    name.contains(['*', '?', '!'])
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p].eq_ignore_ascii_case(&text[t])) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_destination_parts() {
+        assert_eq!(split_destination("jump"), (None, String::from("jump"), None));
+        assert_eq!(split_destination("me@jump:2222"), (Some(String::from("me")), String::from("jump"), Some(2222)));
+        assert_eq!(split_destination("[::1]:22"), (None, String::from("::1"), Some(22)));
+        assert_eq!(split_destination("me@2001:db8::1"), (Some(String::from("me")), String::from("2001:db8::1"), None));
+    }
+
+    #[test]
+    fn glob_match_patterns() {
+        assert!(glob_match("*", "web1"));
+        assert!(glob_match("web?", "web1"));
+        assert!(glob_match("*.example.com", "db.example.com"));
+        assert!(glob_match("w*b*", "web1b"));
+        assert!(!glob_match("web?", "web10"));
+        assert!(!glob_match("*.example.com", "example.com"));
+    }
+
+    #[test]
+    fn matches_with_negated_patterns() {
+        let host = parse_ssh_hosts("Host *.example.com !bastion.example.com\n    User admin\n").remove(0);
+        assert!(host.matches("web.example.com"));
+        assert!(!host.matches("bastion.example.com"));
+        assert_eq!(host.option("user"), Some("admin"));
+    }
+
+    #[test]
+    fn option_line_quotes_only_paths() {
+        assert_eq!(option_line("IdentityFile", "~/my keys/id"), "IdentityFile \"~/my keys/id\"");
+        assert_eq!(option_line("LocalForward", "8080 localhost:80"), "LocalForward 8080 localhost:80");
+        assert_eq!(option_line("ProxyCommand", "ssh -W %h:%p bastion"), "ProxyCommand ssh -W %h:%p bastion");
+    }
+
+    #[test]
+    fn parse_ssh_hosts_reads_options_and_annotations() {
+        let hosts = parse_ssh_hosts("Host web\n    #@tags prod\n    HostName=10.0.0.1\n    Port 22\nHost db\n");
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].option("hostname"), Some("10.0.0.1"));
+        assert_eq!(hosts[0].options.len(), 2);
+        assert_eq!(hosts[0].annotations, [(String::from("tags"), String::from("prod"))]);
+        assert_eq!(hosts[1].index, 1);
+    }
+}
@@ -0,0 +1,182 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::env;
+use std::error::Error;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// directory where ssh-view keeps its state, "~/.ssh-view" unless SSH_VIEW_STATE_DIR is set
+pub fn state_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = match env::var_os("SSH_VIEW_STATE_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            #[allow(deprecated)]
+                let mut dir = env::home_dir().ok_or("couldn't find the home directory")?;
+            dir.push(".ssh-view");
+            dir
+        }
+    };
+    create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+pub fn state_file(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(state_dir()?.join(name))
+}
+
+/// loads a state file, returning the default value if the file doesn't exist yet
+pub fn load_state<T: DeserializeOwned + Default>(name: &str) -> Result<T, Box<dyn Error>> {
+    let path = state_file(name)?;
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+pub fn save_state<T: Serialize>(name: &str, state: &T) -> Result<(), Box<dyn Error>> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(state_file(name)?)?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, state)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// formats a unix timestamp as "YYYY-MM-DD HH:MM:SS" in UTC
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (days, seconds) = ((timestamp / 86400) as i64, timestamp % 86400);
+    // days to civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// formats a number of seconds as "1d 2h", "3h 4m", "5m 6s"...
+pub fn format_elapsed(seconds: u64) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{}m {}s", minutes, seconds % 60)
+    }
+}
+
+/// checks if the process is still running
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    // containers without an init process don't reap the orphans, so a zombie is also a dead process
+    let zombie = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .map(|stat| stat.rsplit_once(')').is_some_and(|(_, stat)| stat.trim_start().starts_with('Z')))
+        .unwrap_or(false);
+    !zombie && unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+#[cfg(windows)]
+fn is_process_alive(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", format!("PID eq {pid}").as_str(), "/NH"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains(pid.to_string().as_str()))
+        .unwrap_or(false)
+}
+
+/// checks if the process is still running and is the one started at the unix time, a pid can be reused by another
+/// process after the first one exits, and that one starts later
+pub fn is_process_started_at(pid: u32, started: u64) -> bool {
+    is_process_alive(pid) && !matches!(process_start_time(pid), Some(start) if start > started)
+}
+
+/// returns the unix time the process started at, none when the system doesn't tell it
+#[cfg(target_os = "linux")]
+fn process_start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // the fields after the command name start with the state, the start time since boot in clock ticks is the 20th
+    let ticks = stat.rsplit_once(')')?.1.split_whitespace().nth(19)?.parse::<u64>().ok()?;
+    let boot_time = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    let ticks_per_second = u64::try_from(unsafe { libc::sysconf(libc::_SC_CLK_TCK) }).ok().filter(|ticks| *ticks > 0)?;
+    Some(boot_time + ticks / ticks_per_second)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_start_time(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// asks the process to terminate
+#[cfg(unix)]
+pub fn terminate_process(pid: u32) -> Result<(), Box<dyn Error>> {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(Box::new(std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn terminate_process(pid: u32) -> Result<(), Box<dyn Error>> {
+    std::process::Command::new("taskkill")
+        .args(["/PID", pid.to_string().as_str(), "/F"])
+        .output()?;
+    Ok(())
+}
+
+/// detaches the command from the terminal so it keeps running after ssh-view exits
+#[cfg(unix)]
+pub fn detach(command: &mut std::process::Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+#[cfg(windows)]
+pub fn detach(command: &mut std::process::Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x00000008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_started_at() {
+        assert!(is_process_started_at(std::process::id(), now()));
+        #[cfg(target_os = "linux")]
+        assert!(!is_process_started_at(std::process::id(), 0));
+    }
+}
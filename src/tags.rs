@@ -0,0 +1,154 @@
+use crate::annotations::{read_annotations, write_annotations};
+use crate::error::CommandError;
+use clap::Subcommand;
+use clap_complete::engine::ArgValueCandidates;
+use prettytable::{color, Attr, Cell, Row, Table};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+/// key of the structured comment with the tags of a host, "#@tags prod,db"
+const TAGS: &str = "tags";
+
+#[derive(Subcommand, Debug)]
+pub enum TagCommand {
+    /// adds the tags to the selected index of the table or the specified connection name in the table
+    Add {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(crate::completion::selection_candidates))]
+        selection: String,
+
+        /// Tags to add
+        #[clap(value_parser, value_name = "Tag", required = true, add = ArgValueCandidates::new(crate::completion::tag_candidates))]
+        tags: Vec<String>,
+    },
+    /// removes the tags from the selected index of the table or the specified connection name in the table
+    Remove {
+        /// Index of the selected connection
+        #[clap(value_parser, value_name = "Selection", add = ArgValueCandidates::new(crate::completion::selection_candidates))]
+        selection: String,
+
+        /// Tags to remove
+        #[clap(value_parser, value_name = "Tag", required = true, add = ArgValueCandidates::new(crate::completion::tag_candidates))]
+        tags: Vec<String>,
+    },
+    /// lists the tags and the connections that have them
+    List,
+}
+
+pub fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// joins several lists of tags in one without repeated tags
+pub fn join_tags(lists: &[&str]) -> String {
+    let mut tags: Vec<String> = vec![];
+    for tag in lists.iter().flat_map(|list| split_tags(list)) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.join(",")
+}
+
+/// checks if the row of the table has every tag of a "tag:<Tag>[,<Tag>...]" filter
+pub fn has_tags(row: &[String], filter: &str) -> bool {
+    let tags = split_tags(row.get(4).map(|tags| tags.as_str()).unwrap_or_default());
+    split_tags(filter.trim_start_matches("tag:"))
+        .iter()
+        .all(|tag| tags.contains(tag))
+}
+
+/// adds and removes tags of the Host block with the index, keeping them in a single structured comment
+pub fn update_tags(config_file: &Path, index: usize, add: &[String], remove: &[String]) -> Result<(), Box<dyn Error>> {
+    if let Some(tag) = add.iter().find(|tag| tag.is_empty() || tag.contains([',', ' ', '\t'])) {
+        return Err(Box::new(CommandError::new(
+            "Tag",
+            format!("\"{tag}\" is not a valid tag, tags can't be empty or have commas or spaces").as_str(),
+        )));
+    }
+    let current = read_annotations(config_file, index, TAGS)?;
+    let mut lists = current.iter().map(|tags| tags.as_str()).collect::<Vec<&str>>();
+    lists.extend(add.iter().map(|tag| tag.as_str()));
+    let tags = split_tags(join_tags(&lists).as_str())
+        .into_iter()
+        .filter(|tag| !remove.contains(tag))
+        .collect::<Vec<String>>();
+    let values = match tags.is_empty() {
+        true => vec![],
+        false => vec![tags.join(",")],
+    };
+    write_annotations(config_file, index, TAGS, &values)
+}
+
+pub fn print_tags(data: &[Vec<String>]) {
+    let mut hosts: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for row in data.iter().skip(1) {
+        for tag in split_tags(row[4].as_str()) {
+            hosts.entry(tag).or_default().push(row[1].clone());
+        }
+    }
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Tag", "Hosts"]
+            .iter()
+            .map(|title| {
+                Cell::new(title)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::GREEN))
+            })
+            .collect::<Vec<Cell>>(),
+    ));
+    for (tag, hosts) in hosts {
+        table.add_row(Row::new(
+            [tag, hosts.join(", ")]
+                .iter()
+                .map(|cell| Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(color::CYAN)))
+                .collect::<Vec<Cell>>(),
+        ));
+    }
+    table.printstd();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::read_annotations;
+    use crate::test_support::{strings, TempFile};
+    use std::fs::read_to_string;
+
+    #[test]
+    fn join_tags_without_repeating_them() {
+        assert_eq!(split_tags(" prod, ,db,"), ["prod", "db"]);
+        assert_eq!(join_tags(&["prod,db", "db,team-x", ""]), "prod,db,team-x");
+    }
+
+    #[test]
+    fn has_every_tag_of_the_filter() {
+        let row = strings(&["0", "web", "", "", "prod,db"]);
+        assert!(has_tags(&row, "tag:prod"));
+        assert!(has_tags(&row, "tag:db,prod"));
+        assert!(!has_tags(&row, "tag:prod,staging"));
+    }
+
+    #[test]
+    fn update_tags_round_trip() {
+        let file = TempFile::new("tags", "Host web\n    HostName 10.0.0.1\n");
+        let path = file.path();
+        update_tags(path, 0, &strings(&["prod", "db"]), &[]).unwrap();
+        update_tags(path, 0, &strings(&["db", "team-x"]), &[]).unwrap();
+        let added = read_to_string(path).unwrap();
+        update_tags(path, 0, &[], &strings(&["db"])).unwrap();
+        let tags = read_annotations(path, 0, TAGS).unwrap();
+        update_tags(path, 0, &[], &strings(&["prod", "team-x"])).unwrap();
+        let removed = read_to_string(path).unwrap();
+        let invalid = update_tags(path, 0, &strings(&["a,b"]), &[]);
+        assert_eq!(added, "Host web\n    #@tags prod,db,team-x\n    HostName 10.0.0.1\n");
+        assert_eq!(tags, ["prod,team-x"]);
+        assert_eq!(removed, "Host web\n    HostName 10.0.0.1\n");
+        assert!(invalid.is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use std::fs::{remove_file, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// file of the temp directory with the data of a test, removed when it is dropped even if the test fails
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// writes the data to "ssh-view-<name>-<pid>", every test uses its own name so they can run in parallel
+    pub fn new(name: &str, data: &str) -> TempFile {
+        let path = std::env::temp_dir().join(format!("ssh-view-{}-{}", name, std::process::id()));
+        write(&path, data).unwrap();
+        TempFile { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.path);
+    }
+}
+
+pub fn strings(strings: &[&str]) -> Vec<String> {
+    strings.iter().map(|string| string.to_string()).collect()
+}
+
+/// the arguments of the command as strings
+pub fn args(command: &Command) -> Vec<String> {
+    command.get_args().map(|arg| arg.to_string_lossy().to_string()).collect()
+}
@@ -0,0 +1,669 @@
+use crate::error::CommandError;
+use crate::forward::{allocate_local_ports, Forward, Listen, LocalPort, PortRange};
+use crate::launch::LaunchMode;
+use crate::metadata::Metadata;
+use crate::OutputFormat;
+use crate::state::{
+    detach, format_elapsed, format_timestamp, is_process_started_at, load_state, now, save_state, state_file,
+    terminate_process,
+};
+use clap::Subcommand;
+use clap_complete::engine::ArgValueCandidates;
+use prettytable::{color, Attr, Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{read_to_string, remove_file, File};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const TUNNELS_STATE: &str = "tunnels.json";
+
+#[derive(Subcommand)]
+pub enum TunnelCommand {
+    #[clap(flatten)]
+    Mode(TunnelMode),
+    /// starts a tunnel saved with the --save option
+    Up {
+        /// Name of the saved tunnel
+        #[clap(value_parser, value_name = "Profile", add = ArgValueCandidates::new(crate::completion::profile_candidates))]
+        profile: String,
+    },
+    /// lists the saved tunnels
+    Profiles,
+    /// deletes a saved tunnel
+    Forget {
+        /// Name of the saved tunnel
+        #[clap(value_parser, value_name = "Profile", add = ArgValueCandidates::new(crate::completion::profile_candidates))]
+        profile: String,
+    },
+    /// lists the tunnels running in background
+    List,
+    /// stops a tunnel running in background
+    Stop {
+        /// Id of the tunnel shown by "tunnel list"
+        #[clap(value_parser, value_name = "Id", required_unless_present = "all")]
+        id: Option<u32>,
+
+        /// Stops every tunnel running in background
+        #[clap(long, value_parser, default_value_t = false)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum TunnelMode {
+    Local {
+        /// Local port to use as one of the sides of the tunnel, "auto" picks a free port
+        #[clap(value_parser, value_name = "LocalPort")]
+        local_port: LocalPort,
+
+        /// Remote host to forward traffic from the tunnel
+        #[clap(value_parser, value_name = "RemoteHost", default_value_t = String::from("127.0.0.1"))]
+        remote_host: String,
+
+        /// Remote port to forward traffic from the tunnel
+        #[clap(value_parser, value_name = "RemotePort", default_value = "80")]
+        remote_port: u16,
+    },
+    Remote {
+        /// Local port to forward traffic from the tunnel
+        #[clap(value_parser, value_name = "LocalPort")]
+        local_port: u16,
+
+        /// Local host to forward traffic from the tunnel
+        #[clap(value_parser, value_name = "LocalHost", default_value_t = String::from("127.0.0.1"))]
+        local_host: String,
+
+        /// Remote port to forward traffic to the tunnel
+        #[clap(value_parser, value_name = "RemotePort", default_value = "80")]
+        remote_port: u16,
+    },
+    Dynamic {
+        /// Local port to use as one of the sides of the tunnel, "auto" picks a free port
+        #[clap(value_parser, value_name = "LocalPort")]
+        local_port: LocalPort,
+    },
+}
+
+impl TunnelMode {
+    /// returns the ssh option and its value for the forward
+    pub fn forward_args(&self) -> (&'static str, String) {
+        match self {
+            TunnelMode::Local {
+                local_port,
+                remote_host,
+                remote_port
+            } => ("-L", format!("{}:{}:{}", local_port, remote_host, remote_port)),
+            TunnelMode::Remote {
+                local_port,
+                local_host,
+                remote_port
+            } => ("-R", format!("{}:{}:{}", remote_port, local_host, local_port)),
+            TunnelMode::Dynamic {
+                local_port
+            } => ("-D", local_port.to_string()),
+        }
+    }
+}
+
+/// Tunnel saved in the metadata file to be started by name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredProfile")]
+pub struct TunnelProfile {
+    pub host: String,
+    #[serde(default)]
+    pub forwards: Vec<Forward>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+}
+
+/// Tunnel profile as it is read from the metadata file, the profiles saved before a tunnel could have several
+/// forwards have a single one in the fields of the mode
+#[derive(Deserialize)]
+struct StoredProfile {
+    host: String,
+    #[serde(flatten, default)]
+    mode: Option<TunnelMode>,
+    #[serde(default)]
+    forwards: Vec<Forward>,
+    #[serde(default)]
+    args: Option<Vec<String>>,
+}
+
+impl From<StoredProfile> for TunnelProfile {
+    fn from(profile: StoredProfile) -> Self {
+        TunnelProfile {
+            host: profile.host,
+            forwards: profile.mode.iter().map(Forward::from).chain(profile.forwards).collect(),
+            args: profile.args,
+        }
+    }
+}
+
+/// Tunnel started in background, stored in the state file while it is running
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunningTunnel {
+    pub id: u32,
+    pub pid: u32,
+    pub host: String,
+    pub forwards: Vec<Forward>,
+    pub started: u64,
+    pub log: PathBuf,
+}
+
+fn forward_args(forwards: &[Forward]) -> Vec<String> {
+    forwards
+        .iter()
+        .flat_map(|forward| [forward.kind.option().to_string(), forward.spec.clone()])
+        .collect()
+}
+
+fn describe_forwards(forwards: &[Forward]) -> String {
+    forwards
+        .iter()
+        .map(|forward| forward.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// the Host line may have several names, ssh connects to the first one and the tunnels are stored with it
+pub fn first_alias(connection_name: &str) -> &str {
+    connection_name.split_whitespace().next().unwrap_or_default()
+}
+
+pub fn tunnel_command(
+    command: &str,
+    connection_name: &str,
+    forwards: &[Forward],
+    args: &Option<Vec<String>>,
+) -> Command {
+    let mut command = Command::new(command);
+    command
+        .args(forward_args(forwards))
+        .arg(first_alias(connection_name))
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command
+}
+
+pub fn run_tunnel(
+    command: &str,
+    connection_name: &str,
+    forwards: &[Forward],
+    args: &Option<Vec<String>>,
+) -> Result<ExitStatus, Box<dyn Error>> {
+    Ok(tunnel_command(command, connection_name, forwards, args)
+        .spawn()?
+        .wait()?)
+}
+
+/// How the tunnel process is run
+pub enum Supervision {
+    /// waits for the tunnel in the terminal
+    Foreground,
+    /// detaches the tunnel and tracks it in the state file
+    Background,
+    /// waits for the tunnel restarting it when it exits or the local port stops answering
+    KeepAlive {
+        probe_interval: Duration,
+        max_backoff: Duration,
+    },
+}
+
+/// How the tunnel is started
+pub struct TunnelOptions {
+    pub supervision: Supervision,
+    /// range of ports to pick from for the "auto" local ports
+    pub port_range: PortRange,
+    pub format: OutputFormat,
+    pub launch: LaunchMode,
+}
+
+/// Forward of a started tunnel with the local port it listens on, printed for the scripts using "auto" ports
+#[derive(Serialize)]
+struct ForwardReport {
+    #[serde(flatten)]
+    forward: Forward,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_port: Option<u16>,
+}
+
+#[derive(Serialize)]
+struct TunnelReport<'a> {
+    host: &'a str,
+    forwards: Vec<ForwardReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+}
+
+/// checks the local ports are free, picks the "auto" ones and starts the tunnel, returns the exit status of the
+/// tunnel when it runs in the foreground
+pub fn start_tunnel(
+    command: &str,
+    connection_name: &str,
+    forwards: &[Forward],
+    args: &Option<Vec<String>>,
+    options: &TunnelOptions,
+) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+    let TunnelOptions {
+        supervision,
+        port_range,
+        format,
+        launch,
+    } = options;
+    let connection_name = first_alias(connection_name);
+    let allocated = allocate_local_ports(forwards, port_range)?;
+    let mut report = TunnelReport {
+        host: connection_name,
+        forwards: allocated
+            .iter()
+            .map(|forward| ForwardReport {
+                forward: forward.clone(),
+                local_port: match forward.listen() {
+                    Ok(Listen::Port { port: LocalPort::Port(port), .. }) if forward.listens_locally() => Some(port),
+                    _ => None,
+                },
+            })
+            .collect(),
+        id: None,
+        pid: None,
+    };
+    if *format == OutputFormat::Table && *launch != LaunchMode::Print {
+        for (requested, allocated) in forwards.iter().zip(report.forwards.iter()) {
+            if let (true, Some(port)) = (requested != &allocated.forward, allocated.local_port) {
+                println!("using the local port {} for \"{}\"", port, allocated.forward);
+            }
+        }
+    }
+    if !launch.spawns() {
+        let command = match supervision {
+            Supervision::Foreground => tunnel_command(command, connection_name, &allocated, args),
+            _ => unattended_tunnel_command(command, connection_name, &allocated, args),
+        };
+        // the report has the local ports that were picked for "auto", the tunnel would use the same ones
+        if *format == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        launch.announce(&command);
+        return Ok(None);
+    }
+    match supervision {
+        Supervision::Background => {
+            let tunnel = start_background_tunnel(command, connection_name, &allocated, args)?;
+            report.id = Some(tunnel.id);
+            report.pid = Some(tunnel.pid);
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                OutputFormat::Table => println!("started tunnel {} (pid {})", tunnel.id, tunnel.pid),
+            }
+            Ok(None)
+        }
+        _ => {
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+            match supervision {
+                Supervision::KeepAlive {
+                    probe_interval,
+                    max_backoff,
+                } => run_tunnel_keep_alive(command, connection_name, &allocated, args, *probe_interval, *max_backoff)
+                    .map(|_| None),
+                _ => run_tunnel(command, connection_name, &allocated, args).map(Some),
+            }
+        }
+    }
+}
+
+/// builds the tunnel command without a remote shell, failing when the forward can't be made or the server stops answering
+fn unattended_tunnel_command(
+    command: &str,
+    connection_name: &str,
+    forwards: &[Forward],
+    args: &Option<Vec<String>>,
+) -> Command {
+    let mut command = Command::new(command);
+    command
+        .arg("-N")
+        .arg("-o")
+        .arg("ExitOnForwardFailure=yes")
+        .arg("-o")
+        .arg("ServerAliveInterval=15")
+        .arg("-o")
+        .arg("ServerAliveCountMax=3")
+        .args(forward_args(forwards))
+        .arg(first_alias(connection_name))
+        .stdin(Stdio::null());
+    if let Some(args) = args {
+        command.args(args.iter());
+    }
+    command
+}
+
+/// starts the tunnel without a remote shell and detached from the terminal, keeping track of it in the state file
+pub fn start_background_tunnel(
+    command: &str,
+    connection_name: &str,
+    forwards: &[Forward],
+    args: &Option<Vec<String>>,
+) -> Result<RunningTunnel, Box<dyn Error>> {
+    let mut tunnels: Vec<RunningTunnel> = load_state(TUNNELS_STATE)?;
+    let id = tunnels.iter().map(|tunnel| tunnel.id).max().map_or(1, |id| id + 1);
+    let log = state_file(format!("tunnel-{id}.log").as_str())?;
+    let mut command = unattended_tunnel_command(command, connection_name, forwards, args);
+    command
+        .stdout(Stdio::null())
+        .stderr(File::create(&log)?);
+    detach(&mut command);
+    let mut child = command.spawn()?;
+    // ssh exits quickly when the connection or the forward fails
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(1500) {
+        if let Some(status) = child.try_wait()? {
+            let output = read_to_string(&log).unwrap_or_default();
+            let _ = remove_file(&log);
+            return Err(Box::new(CommandError::new(
+                "Tunnel Mode",
+                format!("the tunnel exited with {}: {}", status, output.trim()).as_str(),
+            )));
+        }
+        sleep(Duration::from_millis(100));
+    }
+    let tunnel = RunningTunnel {
+        id,
+        pid: child.id(),
+        host: first_alias(connection_name).to_string(),
+        forwards: forwards.to_vec(),
+        started: now(),
+        log,
+    };
+    tunnels.push(tunnel.clone());
+    save_state(TUNNELS_STATE, &tunnels)?;
+    Ok(tunnel)
+}
+
+/// runs the tunnel restarting it with an exponential backoff when it exits or its local port stops accepting connections
+pub fn run_tunnel_keep_alive(
+    command: &str,
+    connection_name: &str,
+    forwards: &[Forward],
+    args: &Option<Vec<String>>,
+    probe_interval: Duration,
+    max_backoff: Duration,
+) -> Result<(), Box<dyn Error>> {
+    // the listening side of a remote forward is on the server, so only the local ones are probed
+    let probe_addresses = forwards
+        .iter()
+        .filter(|forward| forward.listens_locally())
+        .filter_map(|forward| match forward.listen() {
+            Ok(Listen::Port { bind, port: LocalPort::Port(port) }) => Some(probe_address(&bind, port)),
+            _ => None,
+        })
+        .collect::<Vec<SocketAddr>>();
+    let mut backoff = Backoff::new(probe_interval, max_backoff);
+    loop {
+        log_event(format!("starting tunnel {} to {}", describe_forwards(forwards), connection_name).as_str());
+        let mut child = unattended_tunnel_command(command, connection_name, forwards, args)
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let started = Instant::now();
+        let mut last_probe = Instant::now();
+        let reason = loop {
+            if let Some(status) = child.try_wait()? {
+                break format!("tunnel exited with {status}");
+            }
+            if last_probe.elapsed() >= probe_interval {
+                last_probe = Instant::now();
+                let failed_probe = probe_addresses
+                    .iter()
+                    .find_map(|address| probe_local_address(address).err().map(|e| (address, e)));
+                if let Some((address, e)) = failed_probe {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break format!("local address {address} is not answering ({e})");
+                }
+            }
+            sleep(Duration::from_millis(500));
+        };
+        let delay = backoff.delay(started.elapsed());
+        log_event(format!("{}, reconnecting in {}s", reason, delay.as_secs()).as_str());
+        sleep(delay);
+    }
+}
+
+/// Exponential backoff between the restarts of a tunnel
+struct Backoff {
+    next: Duration,
+    max: Duration,
+    /// time a tunnel has to stay up to be taken as working again
+    stable: Duration,
+}
+
+impl Backoff {
+    fn new(probe_interval: Duration, max_backoff: Duration) -> Self {
+        Backoff {
+            next: Duration::from_secs(1),
+            max: max_backoff.max(Duration::from_secs(1)),
+            stable: probe_interval.max(Duration::from_secs(60)),
+        }
+    }
+
+    /// returns the time to wait before restarting the tunnel that stopped after being up for the uptime, a tunnel
+    /// that was up for a while is restarted quickly, one that keeps failing waits longer each time
+    fn delay(&mut self, uptime: Duration) -> Duration {
+        if uptime > self.stable {
+            self.next = Duration::from_secs(1);
+        }
+        let delay = self.next.min(self.max);
+        self.next = (delay * 2).min(self.max);
+        delay
+    }
+}
+
+/// address to connect to a local forward, the loopback one unless it only listens on a specific address
+fn probe_address(bind: &Option<String>, port: u16) -> SocketAddr {
+    bind.as_deref()
+        .and_then(|bind| bind.parse::<IpAddr>().ok())
+        .filter(|ip| !ip.is_unspecified())
+        .map(|ip| SocketAddr::new(ip, port))
+        .unwrap_or(SocketAddr::from(([127, 0, 0, 1], port)))
+}
+
+fn probe_local_address(address: &SocketAddr) -> std::io::Result<()> {
+    TcpStream::connect_timeout(address, Duration::from_secs(5)).map(|_| ())
+}
+
+fn log_event(message: &str) {
+    eprintln!("[{}] {}", format_timestamp(now()), message);
+}
+
+/// loads the tunnels running in background removing the ones whose process is gone or is another one with the same pid
+fn running_tunnels() -> Result<Vec<RunningTunnel>, Box<dyn Error>> {
+    let tunnels: Vec<RunningTunnel> = load_state(TUNNELS_STATE)?;
+    let (alive, dead): (Vec<RunningTunnel>, Vec<RunningTunnel>) = tunnels
+        .into_iter()
+        .partition(|tunnel| is_process_started_at(tunnel.pid, tunnel.started));
+    if !dead.is_empty() {
+        for tunnel in dead.iter() {
+            println!("tunnel {} to {} is not running anymore, removing it", tunnel.id, tunnel.host);
+            let _ = remove_file(&tunnel.log);
+        }
+        save_state(TUNNELS_STATE, &alive)?;
+    }
+    Ok(alive)
+}
+
+pub fn print_running_tunnels() -> Result<(), Box<dyn Error>> {
+    let tunnels = running_tunnels()?;
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Id", "Pid", "Host", "Forward", "Uptime"]
+            .iter()
+            .map(|title| {
+                Cell::new(title)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::GREEN))
+            })
+            .collect::<Vec<Cell>>(),
+    ));
+    for tunnel in tunnels.iter() {
+        table.add_row(Row::new(
+            [
+                tunnel.id.to_string(),
+                tunnel.pid.to_string(),
+                tunnel.host.clone(),
+                describe_forwards(&tunnel.forwards),
+                format_elapsed(now().saturating_sub(tunnel.started)),
+            ]
+                .iter()
+                .map(|cell| Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(color::CYAN)))
+                .collect::<Vec<Cell>>(),
+        ));
+    }
+    table.printstd();
+    Ok(())
+}
+
+pub fn stop_tunnels(id: &Option<u32>, all: bool, launch: &LaunchMode) -> Result<(), Box<dyn Error>> {
+    let tunnels = running_tunnels()?;
+    let (stop, keep): (Vec<RunningTunnel>, Vec<RunningTunnel>) = tunnels
+        .into_iter()
+        .partition(|tunnel| all || Some(tunnel.id) == *id);
+    if stop.is_empty() && !all {
+        return Err(Box::new(CommandError::new(
+            "Tunnel Mode",
+            format!("no tunnel running with the id {}", id.unwrap_or_default()).as_str(),
+        )));
+    }
+    if !launch.spawns() {
+        for tunnel in stop.iter() {
+            eprintln!("would stop tunnel {} to {} with pid {}", tunnel.id, tunnel.host, tunnel.pid);
+        }
+        return Ok(());
+    }
+    for tunnel in stop.iter() {
+        terminate_process(tunnel.pid)?;
+        let _ = remove_file(&tunnel.log);
+        println!("stopped tunnel {} to {}", tunnel.id, tunnel.host);
+    }
+    save_state(TUNNELS_STATE, &keep)
+}
+
+pub fn find_profile<'a>(metadata: &'a Metadata, profile: &str) -> Result<&'a TunnelProfile, Box<dyn Error>> {
+    metadata.tunnels.get(profile).ok_or_else(|| {
+        Box::new(CommandError::new("Tunnel Mode", format!("no tunnel saved with the name {profile}").as_str())) as Box<dyn Error>
+    })
+}
+
+pub fn print_profiles(metadata: &Metadata) {
+    let mut table = Table::new();
+    table.add_row(Row::new(
+        ["Profile", "Host", "Forward", "Args"]
+            .iter()
+            .map(|title| {
+                Cell::new(title)
+                    .with_style(Attr::Bold)
+                    .with_style(Attr::ForegroundColor(color::GREEN))
+            })
+            .collect::<Vec<Cell>>(),
+    ));
+    for (name, profile) in metadata.tunnels.iter() {
+        table.add_row(Row::new(
+            [
+                name.clone(),
+                profile.host.clone(),
+                describe_forwards(&profile.forwards),
+                profile.args.clone().unwrap_or_default().join(" "),
+            ]
+                .iter()
+                .map(|cell| Cell::new(cell.as_str()).with_style(Attr::ForegroundColor(color::CYAN)))
+                .collect::<Vec<Cell>>(),
+        ));
+    }
+    table.printstd();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::args;
+
+    #[test]
+    fn tunnel_command_puts_the_forwards_before_the_host() {
+        let forwards = [
+            Forward::parse_local("8080:localhost:80").unwrap(),
+            Forward::parse_dynamic("1080").unwrap(),
+        ];
+        let command = tunnel_command("ssh", "web", &forwards, &Some(vec![String::from("-N")]));
+        assert_eq!(command.get_program(), "ssh");
+        assert_eq!(args(&command), ["-L", "8080:localhost:80", "-D", "1080", "web", "-N"]);
+    }
+
+    #[test]
+    fn tunnels_connect_to_the_first_alias() {
+        let forwards = [Forward::parse_local("8080:db:5432").unwrap()];
+        let command = tunnel_command("ssh", "web web.prod", &forwards, &None);
+        assert_eq!(args(&command), ["-L", "8080:db:5432", "web"]);
+        let command = unattended_tunnel_command("ssh", "web web.prod", &forwards, &None);
+        assert_eq!(args(&command).last().map(String::as_str), Some("web"));
+    }
+
+    #[test]
+    fn tunnel_command_without_forwards() {
+        let command = tunnel_command("autossh", "db", &[], &None);
+        assert_eq!(command.get_program(), "autossh");
+        assert_eq!(args(&command), ["db"]);
+    }
+
+    #[test]
+    fn profiles_read_the_forward_of_the_legacy_mode() {
+        let profile: TunnelProfile =
+            serde_json::from_str(r#"{"host":"web","mode":"local","local_port":8080,"remote_host":"db","remote_port":5432}"#)
+                .unwrap();
+        assert_eq!(profile.forwards, [Forward::parse_local("8080:db:5432").unwrap()]);
+        let profile: TunnelProfile = serde_json::from_str(r#"{"host":"web","args":["-N"]}"#).unwrap();
+        assert!(profile.forwards.is_empty());
+        let saved = TunnelProfile {
+            host: String::from("web"),
+            forwards: vec![Forward::parse_dynamic("auto").unwrap()],
+            args: None,
+        };
+        assert_eq!(serde_json::from_str::<TunnelProfile>(&serde_json::to_string(&saved).unwrap()).unwrap(), saved);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let mut backoff = Backoff::new(Duration::from_secs(30), Duration::from_secs(10));
+        let delays = (0..6).map(|_| backoff.delay(Duration::from_secs(1)).as_secs()).collect::<Vec<u64>>();
+        assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+    }
+
+    #[test]
+    fn backoff_resets_after_a_stable_run() {
+        let mut backoff = Backoff::new(Duration::from_secs(120), Duration::from_secs(300));
+        for _ in 0..4 {
+            backoff.delay(Duration::from_secs(5));
+        }
+        assert_eq!(backoff.delay(Duration::from_secs(90)), Duration::from_secs(16));
+        // the tunnel has to stay up longer than the probe interval, and at least a minute
+        assert_eq!(backoff.delay(Duration::from_secs(121)), Duration::from_secs(1));
+        assert_eq!(backoff.delay(Duration::from_secs(5)), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn backoff_waits_at_least_a_second() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::ZERO);
+        assert_eq!(backoff.delay(Duration::ZERO), Duration::from_secs(1));
+        assert_eq!(backoff.delay(Duration::from_secs(61)), Duration::from_secs(1));
+    }
+}